@@ -2,9 +2,9 @@
 extern crate criterion;
 
 extern crate aeroscore;
-extern crate igc;
 
 use criterion::Criterion;
+use aeroscore::geodesic;
 use aeroscore::haversine::haversine_distance;
 
 struct Point {
@@ -28,6 +28,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     let point1 = Point { latitude: 51.301389, longitude: 6.953333 };
     let point2 = Point { latitude: 50.823194, longitude: 6.186389 };
     c.bench_function("haversine", |b| b.iter(|| haversine_distance(&point1, &point2)));
+    c.bench_function("geodesic", |b| b.iter(|| geodesic::distance(&point1, &point2)));
 }
 
 criterion_group!(benches, criterion_benchmark);
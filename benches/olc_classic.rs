@@ -2,41 +2,14 @@
 extern crate criterion;
 
 extern crate aeroscore;
-extern crate igc;
 
 use criterion::Criterion;
+use aeroscore::igc::Flight;
 use aeroscore::olc;
 
-struct Point {
-    latitude: f32,
-    longitude: f32,
-    altitude: i16,
-}
-
-impl aeroscore::Point for Point {
-    fn latitude(&self) -> f32 {
-        self.latitude
-    }
-    fn longitude(&self) -> f32 {
-        self.longitude
-    }
-    fn altitude(&self) -> i16 {
-        self.altitude
-    }
-}
-
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("olc_classic", |b| b.iter(|| {
-        let fixes = include_str!("../tests/fixtures/2017-08-14-fla-6ng-01.igc")
-            .lines()
-            .filter(|l| l.starts_with('B'))
-            .filter_map(|line| igc::records::BRecord::parse(&line).ok()
-                .map(|record| Point {
-                    latitude: record.pos.lat.into(),
-                    longitude: record.pos.lon.into(),
-                    altitude: record.pressure_alt,
-                }))
-            .collect::<Vec<_>>();
+        let fixes = Flight::parse(include_str!("../tests/fixtures/2017-08-14-fla-6ng-01.igc")).fixes;
 
         olc::optimize(&fixes).unwrap()
     }));
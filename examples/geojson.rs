@@ -2,32 +2,12 @@
 extern crate serde_json;
 
 extern crate aeroscore;
-extern crate igc;
 
 use std::env;
-use std::io::prelude::*;
-use std::io::BufReader;
 use std::fs::File;
 
-use aeroscore::olc;
-
-struct Point {
-    latitude: f32,
-    longitude: f32,
-    altitude: i16,
-}
-
-impl aeroscore::Point for Point {
-    fn latitude(&self) -> f32 {
-        self.latitude
-    }
-    fn longitude(&self) -> f32 {
-        self.longitude
-    }
-    fn altitude(&self) -> i16 {
-        self.altitude
-    }
-}
+use aeroscore::igc::Flight;
+use aeroscore::{olc, track};
 
 #[allow(dead_code)]
 fn main() {
@@ -40,19 +20,10 @@ fn main() {
 }
 
 fn analyze(path: &str) {
-    let file = File::open(&path).expect("failed to open file");
+    let file = File::open(path).expect("failed to open file");
 
-    let fixes = BufReader::new(file)
-        .lines()
-        .filter_map(|l| l.ok())
-        .filter(|l| l.starts_with('B'))
-        .filter_map(|line| igc::records::BRecord::parse(&line).ok()
-            .map(|record| Point {
-                latitude: record.pos.lat.into(),
-                longitude: record.pos.lon.into(),
-                altitude: record.pressure_alt,
-            }))
-        .collect::<Vec<_>>();
+    let fixes = Flight::read(file).expect("failed to read file").fixes;
+    let fixes = track::sanitize(fixes).fixes;
 
     let result = olc::optimize(&fixes).unwrap();
 
@@ -90,7 +61,7 @@ fn analyze(path: &str) {
       ],
     });
 
-    println!("{}", json.to_string());
+    println!("{}", json);
 }
 
 fn help() {
@@ -0,0 +1,51 @@
+use crate::Point;
+
+/// Pressure altitude range in meters below which the pressure sensor is
+/// considered to be broken
+const MIN_PRESSURE_ALTITUDE_RANGE: i16 = 10;
+
+/// Source of the altitudes that are used for the altitude rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AltitudeSource {
+    /// `Point::altitude()`
+    Pressure,
+    /// `Point::gnss_altitude()`
+    Gnss,
+    /// Pressure altitude, or GNSS altitude if the pressure altitude is flat
+    /// (e.g. zero) throughout the whole flight
+    Auto,
+}
+
+/// Returns the altitudes of all fixes from the given `source` together with
+/// the source that was actually used.
+///
+/// Returns `None` if GNSS altitudes were requested but are not available for
+/// every fix.
+pub fn altitudes<T: Point>(route: &[T], source: AltitudeSource) -> Option<(AltitudeSource, Vec<i16>)> {
+    match source {
+        AltitudeSource::Pressure => Some((source, pressure_altitudes(route))),
+        AltitudeSource::Gnss => gnss_altitudes(route).map(|altitudes| (source, altitudes)),
+        AltitudeSource::Auto => {
+            let altitudes = pressure_altitudes(route);
+
+            let min = altitudes.iter().min().cloned().unwrap_or(0);
+            let max = altitudes.iter().max().cloned().unwrap_or(0);
+            if i32::from(max) - i32::from(min) >= i32::from(MIN_PRESSURE_ALTITUDE_RANGE) {
+                return Some((AltitudeSource::Pressure, altitudes));
+            }
+
+            match gnss_altitudes(route) {
+                Some(altitudes) => Some((AltitudeSource::Gnss, altitudes)),
+                None => Some((AltitudeSource::Pressure, altitudes)),
+            }
+        }
+    }
+}
+
+fn pressure_altitudes<T: Point>(route: &[T]) -> Vec<i16> {
+    route.iter().map(|fix| fix.altitude()).collect()
+}
+
+fn gnss_altitudes<T: Point>(route: &[T]) -> Option<Vec<i16>> {
+    route.iter().map(|fix| fix.gnss_altitude()).collect()
+}
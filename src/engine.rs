@@ -0,0 +1,165 @@
+//! Detection of engine runs of motor gliders and self-launchers
+
+use std::ops::Range;
+
+use crate::Point;
+use crate::altitude::{altitudes, AltitudeSource};
+use crate::flat::project_points;
+use crate::heading::turns;
+use crate::time::increasing_timeline;
+
+/// ENL or MOP level from which the engine is considered to be running
+const MIN_ENGINE_LEVEL: u16 = 500;
+
+/// Minimum duration in seconds of an engine run, which filters short noise
+/// peaks e.g. from sideslipping or opening the air brakes
+const MIN_ENGINE_RUN: u32 = 30;
+
+/// Sensors that report an engine level above `MIN_ENGINE_LEVEL` for more than
+/// this share of the fixes are considered to be broken or not calibrated
+const MAX_ENGINE_SHARE: f64 = 0.5;
+
+/// Time window in seconds for the climb heuristic
+const CLIMB_WINDOW: u32 = 180;
+
+/// Climb rate in m/s from which a straight climb is considered to be powered
+const MIN_ENGINE_CLIMB: f64 = 2.0;
+
+/// Sum of all heading changes in degrees within `CLIMB_WINDOW` below which a
+/// climb is considered to be straight
+const MAX_ENGINE_TURN: f64 = 360.;
+
+/// Source of the engine runs that were detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineSource {
+    /// `Point::mop()`
+    MeansOfPropulsion,
+    /// `Point::enl()`
+    EngineNoiseLevel,
+    /// Straight climbs without circling, because no usable sensor is available
+    Climb,
+}
+
+/// Returns the index ranges of all engine runs together with the source that
+/// was used to detect them.
+///
+/// The MOP sensor is preferred over the ENL sensor. If neither is available
+/// or reports a running engine most of the time, straight climbs of at least
+/// `MIN_ENGINE_CLIMB` are considered to be powered, which also includes
+/// aerotows and may include wave or ridge climbs.
+///
+/// Returns `None` if any of the fixes has no time or is not later than the
+/// fix before it.
+pub fn engine_runs<T: Point>(route: &[T]) -> Option<(EngineSource, Vec<Range<usize>>)> {
+    if route.is_empty() {
+        return Some((EngineSource::Climb, Vec::new()));
+    }
+
+    let times = increasing_timeline(route)?;
+
+    for &source in &[EngineSource::MeansOfPropulsion, EngineSource::EngineNoiseLevel] {
+        if let Some(running) = sensor_running(route, source) {
+            return Some((source, to_runs(&running, &times)));
+        }
+    }
+
+    Some((EngineSource::Climb, to_runs(&climb_running(route, &times), &times)))
+}
+
+/// Returns the index ranges of the fixes between the `runs`, i.e. the
+/// segments of a track with `len` fixes that were flown without engine
+pub fn powerless_segments(len: usize, runs: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut start = 0;
+    let mut segments = Vec::new();
+    for run in runs {
+        if run.start > start {
+            segments.push(start..run.start);
+        }
+        start = start.max(run.end);
+    }
+    if start < len {
+        segments.push(start..len);
+    }
+    segments
+}
+
+/// Returns for every fix whether the sensor reports a running engine, or
+/// `None` if the sensor is not available or not usable
+fn sensor_running<T: Point>(route: &[T], source: EngineSource) -> Option<Vec<bool>> {
+    let level = |fix: &T| match source {
+        EngineSource::MeansOfPropulsion => fix.mop(),
+        EngineSource::EngineNoiseLevel => fix.enl(),
+        EngineSource::Climb => None,
+    };
+
+    if route.iter().all(|fix| level(fix).is_none()) {
+        return None;
+    }
+
+    let running: Vec<_> = route.iter()
+        .map(|fix| level(fix).is_some_and(|level| level >= MIN_ENGINE_LEVEL))
+        .collect();
+
+    let share = running.iter().filter(|&&running| running).count() as f64 / route.len() as f64;
+    if share > MAX_ENGINE_SHARE {
+        return None;
+    }
+
+    Some(running)
+}
+
+/// Returns for every fix whether it is part of a straight climb of at least
+/// `MIN_ENGINE_CLIMB` within `CLIMB_WINDOW`
+fn climb_running<T: Point>(route: &[T], times: &[u32]) -> Vec<bool> {
+    let mut running = vec![false; route.len()];
+    let (_, altitudes) = match altitudes(route, AltitudeSource::Auto) {
+        Some(altitudes) => altitudes,
+        None => return running,
+    };
+
+    let turns = match project_points::<f64, T>(route) {
+        Some(points) => turns(&points),
+        None => return running,
+    };
+
+    let mut end = 0;
+    for start in 0..route.len() {
+        end = end.max(start);
+        while end + 1 < route.len() && times[end] < times[start] + CLIMB_WINDOW {
+            end += 1;
+        }
+
+        let duration = times[end] - times[start];
+        if duration < CLIMB_WINDOW {
+            continue;
+        }
+
+        let climb = f64::from(i32::from(altitudes[end]) - i32::from(altitudes[start])) / f64::from(duration);
+        let turn: f64 = turns[start..end].iter().map(|turn| turn.abs()).sum();
+        if climb >= MIN_ENGINE_CLIMB && turn < MAX_ENGINE_TURN {
+            running[start..=end].iter_mut().for_each(|running| *running = true);
+        }
+    }
+
+    running
+}
+
+/// Converts the running flags into index ranges that last at least
+/// `MIN_ENGINE_RUN` seconds
+fn to_runs(running: &[bool], times: &[u32]) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for i in 0..=running.len() {
+        match (start, running.get(i).cloned().unwrap_or(false)) {
+            (None, true) => start = Some(i),
+            (Some(first), false) => {
+                if times[i - 1] - times[first] >= MIN_ENGINE_RUN {
+                    runs.push(first..i);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
@@ -2,42 +2,94 @@ use flat_projection::{FlatProjection, FlatPoint};
 use ord_subset::OrdSubsetIterExt;
 
 use crate::Point;
+use crate::float::Float;
 use crate::parallel::*;
 
 /// Projects all geographic points onto a flat surface for faster geodesic calculation
 ///
-pub fn to_flat_points<T: Point>(points: &[T]) -> Vec<FlatPoint<f32>> {
-    let center_lat = points.center_lat().unwrap();
-    let center_lon = points.center_lon().unwrap();
-    let proj = FlatProjection::new(center_lon, center_lat);
+/// Returns `None` if there are no points with finite coordinates, which
+/// leaves no center for the projection.
+pub fn to_flat_points<T: Point>(points: &[T]) -> Option<Vec<FlatPoint<f32>>> {
+    project_points(points)
+}
+
+/// Same as `to_flat_points()`, but calculates the flat points with the
+/// precision of `F` from the double precision coordinates of the points
+pub fn project_points<F: Float, T: Point>(points: &[T]) -> Option<Vec<FlatPoint<F>>> {
+    let center = projection_center::<f64, T>(points)?;
+    Some(project_around(points, center))
+}
 
+/// Projects the `points` onto a flat surface with the given `(longitude,
+/// latitude)` as center of the projection
+pub(crate) fn project_around<F: Float, T: Point>(points: &[T], center: (f64, f64)) -> Vec<FlatPoint<F>> {
+    let (center_lon, center_lat) = center;
+    let proj = FlatProjection::new(F::from_f64(center_lon), F::from_f64(center_lat));
+
+    // longitudes on the other side of the antimeridian are moved next to
+    // the center so that the projection stays continuous
     opt_par_iter(points)
-        .map(|fix| proj.project(fix.longitude(), fix.latitude()))
+        .map(|fix| {
+            let lon = fix.longitude_f64();
+            let lon = if (lon - center_lon).abs() > 180. { center_lon + wrap_longitude(lon - center_lon) } else { lon };
+            proj.project(F::from_f64(lon), F::from_f64(fix.latitude_f64()))
+        })
         .collect()
 }
 
+/// Normalizes a longitude (difference) in degrees to the range `[-180, 180)`
+pub fn wrap_longitude<F: Float>(longitude: F) -> F {
+    let half_turn = F::from_f64(180.);
+    if longitude >= -half_turn && longitude < half_turn {
+        longitude
+    } else {
+        (longitude + half_turn).rem_euclid(&(half_turn + half_turn)) - half_turn
+    }
+}
+
+/// Returns the `(longitude, latitude)` of the center of the bounding box of
+/// all points, which `to_flat_points()` uses as center of the projection
+pub fn projection_center<F: Float, T: Point>(points: &[T]) -> Option<(F, F)> {
+    Some((F::from_f64(points.center_lon()?), F::from_f64(points.center_lat()?)))
+}
+
 trait CenterLatitude {
-    fn center_lat(self: &Self) -> Option<f32>;
+    fn center_lat(&self) -> Option<f64>;
 }
 
 impl<T: Point> CenterLatitude for [T] {
-    fn center_lat(self: &Self) -> Option<f32> {
-        let lat_min = self.iter().map(|fix| fix.latitude()).ord_subset_min()?;
-        let lat_max = self.iter().map(|fix| fix.latitude()).ord_subset_max()?;
+    fn center_lat(&self) -> Option<f64> {
+        let lat_min = self.iter().map(|fix| fix.latitude_f64()).ord_subset_min()?;
+        let lat_max = self.iter().map(|fix| fix.latitude_f64()).ord_subset_max()?;
 
         Some((lat_min + lat_max) / 2.)
     }
 }
 
 trait CenterLongitude {
-    fn center_lon(self: &Self) -> Option<f32>;
+    fn center_lon(&self) -> Option<f64>;
 }
 
 impl<T: Point> CenterLongitude for [T] {
-    fn center_lon(self: &Self) -> Option<f32> {
-        let lon_min = self.iter().map(|fix| fix.longitude()).ord_subset_min()?;
-        let lon_max = self.iter().map(|fix| fix.longitude()).ord_subset_max()?;
+    /// Returns the center of the smaller of the longitude ranges in
+    /// `[-180, 180)` and `[0, 360)`, so that tracks which cross the
+    /// antimeridian get a center next to them
+    fn center_lon(&self) -> Option<f64> {
+        let lon_min = self.iter().map(|fix| fix.longitude_f64()).ord_subset_min()?;
+        let lon_max = self.iter().map(|fix| fix.longitude_f64()).ord_subset_max()?;
+
+        if lon_max - lon_min <= 180. {
+            return Some((lon_min + lon_max) / 2.);
+        }
+
+        let shifted_lon = |fix: &T| fix.longitude_f64().rem_euclid(360.);
+        let shifted_min = self.iter().map(shifted_lon).ord_subset_min()?;
+        let shifted_max = self.iter().map(shifted_lon).ord_subset_max()?;
 
-        Some((lon_min + lon_max) / 2.)
+        if shifted_max - shifted_min < lon_max - lon_min {
+            Some(wrap_longitude((shifted_min + shifted_max) / 2.))
+        } else {
+            Some((lon_min + lon_max) / 2.)
+        }
     }
 }
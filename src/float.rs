@@ -0,0 +1,33 @@
+use std::fmt;
+use std::iter::Sum;
+
+use ord_subset::OrdSubset;
+
+/// Floating point type of the flat projection and the optimizers, i.e.
+/// `f32` for speed or `f64` for accuracy
+pub trait Float: num_traits::Float + num_traits::Euclid + OrdSubset + Sum + fmt::Debug + fmt::Display + Send + Sync + 'static {
+    /// Converts from double precision, rounding to the nearest value
+    fn from_f64(value: f64) -> Self;
+
+    fn into_f64(self) -> f64;
+}
+
+impl Float for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn into_f64(self) -> f64 {
+        f64::from(self)
+    }
+}
+
+impl Float for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn into_f64(self) -> f64 {
+        self
+    }
+}
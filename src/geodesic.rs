@@ -0,0 +1,190 @@
+//! Distances and bearings on the WGS84 ellipsoid, calculated with
+//! Vincenty's formulae in double precision.
+
+use crate::Point;
+use crate::flat::wrap_longitude;
+
+/// Semi-major axis of the WGS84 ellipsoid in kilometers
+const A: f64 = 6378.137;
+
+/// Flattening of the WGS84 ellipsoid
+const F: f64 = 1. / 298.257_223_563;
+
+/// Semi-minor axis of the WGS84 ellipsoid in kilometers
+const B: f64 = A * (1. - F);
+
+/// Maximum number of iterations. Vincenty's formulae converge within a few
+/// iterations unless the points are nearly antipodal.
+const MAX_ITERATIONS: usize = 200;
+
+/// Convergence threshold in radians (about 0.006 mm)
+const EPSILON: f64 = 1e-12;
+
+/// Solution of the inverse geodesic problem between two points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Inverse {
+    /// Length of the geodesic in kilometers
+    pub distance: f64,
+    /// Bearing at the first point in degrees clockwise from north
+    pub initial_bearing: f64,
+    /// Bearing at the second point in degrees clockwise from north
+    pub final_bearing: f64,
+}
+
+/// Solution of the direct geodesic problem from a point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Direct {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Bearing at the destination in degrees clockwise from north
+    pub final_bearing: f64,
+}
+
+/// Calculates the geodesic between `fix1` and `fix2`.
+///
+/// The bearings are in the range `[0, 360)` and are `0` if both points are
+/// the same. For nearly antipodal points the iteration may not converge and
+/// the result of the last iteration is returned.
+pub fn inverse(fix1: &dyn Point, fix2: &dyn Point) -> Inverse {
+    let phi1 = fix1.latitude_f64().to_radians();
+    let phi2 = fix2.latitude_f64().to_radians();
+    let l = wrap_longitude(fix2.longitude_f64() - fix1.longitude_f64()).to_radians();
+
+    let (sin_u1, cos_u1) = reduced_latitude(phi1);
+    let (sin_u2, cos_u2) = reduced_latitude(phi2);
+
+    let mut lambda = l;
+    let (mut sin_sigma, mut cos_sigma, mut sigma) = (0., 1., 0.);
+    let (mut cos_sq_alpha, mut cos_2_sigma_m) = (1., 0.);
+    for _ in 0..MAX_ITERATIONS {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sq_sigma = (cos_u2 * sin_lambda).powi(2) +
+            (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2);
+
+        if sin_sq_sigma < 1e-24 {
+            return Inverse { distance: 0., initial_bearing: 0., final_bearing: 0. };
+        }
+
+        sin_sigma = sin_sq_sigma.sqrt();
+        cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        sigma = sin_sigma.atan2(cos_sigma);
+
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        cos_sq_alpha = 1. - sin_alpha * sin_alpha;
+
+        // both points are on the equator if `cos_sq_alpha` is zero
+        cos_2_sigma_m = if cos_sq_alpha != 0. { cos_sigma - 2. * sin_u1 * sin_u2 / cos_sq_alpha } else { 0. };
+
+        let c = F / 16. * cos_sq_alpha * (4. + F * (4. - 3. * cos_sq_alpha));
+        let previous = lambda;
+        lambda = l + (1. - c) * F * sin_alpha *
+            (sigma + c * sin_sigma * (cos_2_sigma_m + c * cos_sigma * (-1. + 2. * cos_2_sigma_m * cos_2_sigma_m)));
+
+        if (lambda - previous).abs() <= EPSILON {
+            break;
+        }
+    }
+
+    let (a, b) = series_coefficients(cos_sq_alpha);
+    let delta_sigma = sigma_correction(b, sin_sigma, cos_sigma, cos_2_sigma_m);
+    let distance = B * a * (sigma - delta_sigma);
+
+    let (sin_lambda, cos_lambda) = lambda.sin_cos();
+    let alpha1 = (cos_u2 * sin_lambda).atan2(cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda);
+    let alpha2 = (cos_u1 * sin_lambda).atan2(-sin_u1 * cos_u2 + cos_u1 * sin_u2 * cos_lambda);
+
+    Inverse {
+        distance,
+        initial_bearing: to_bearing(alpha1),
+        final_bearing: to_bearing(alpha2),
+    }
+}
+
+/// Calculates the distance in kilometers between `fix1` and `fix2`
+pub fn distance(fix1: &dyn Point, fix2: &dyn Point) -> f64 {
+    inverse(fix1, fix2).distance
+}
+
+/// Calculates the bearing at `fix1` of the geodesic to `fix2`
+pub fn initial_bearing(fix1: &dyn Point, fix2: &dyn Point) -> f64 {
+    inverse(fix1, fix2).initial_bearing
+}
+
+/// Calculates the bearing at `fix2` of the geodesic from `fix1`
+pub fn final_bearing(fix1: &dyn Point, fix2: &dyn Point) -> f64 {
+    inverse(fix1, fix2).final_bearing
+}
+
+/// Calculates the destination after travelling `distance` kilometers from
+/// `fix` with the initial `bearing` in degrees clockwise from north
+pub fn direct(fix: &dyn Point, bearing: f64, distance: f64) -> Direct {
+    let phi1 = fix.latitude_f64().to_radians();
+    let (sin_alpha1, cos_alpha1) = bearing.to_radians().sin_cos();
+
+    let (sin_u1, cos_u1) = reduced_latitude(phi1);
+    let sigma1 = (sin_u1 / cos_u1).atan2(cos_alpha1);
+    let sin_alpha = cos_u1 * sin_alpha1;
+    let cos_sq_alpha = 1. - sin_alpha * sin_alpha;
+
+    let (a, b) = series_coefficients(cos_sq_alpha);
+
+    let mut sigma = distance / (B * a);
+    for _ in 0..MAX_ITERATIONS {
+        let (sin_sigma, cos_sigma) = sigma.sin_cos();
+        let cos_2_sigma_m = (2. * sigma1 + sigma).cos();
+
+        let previous = sigma;
+        sigma = distance / (B * a) + sigma_correction(b, sin_sigma, cos_sigma, cos_2_sigma_m);
+
+        if (sigma - previous).abs() <= EPSILON {
+            break;
+        }
+    }
+
+    let (sin_sigma, cos_sigma) = sigma.sin_cos();
+    let cos_2_sigma_m = (2. * sigma1 + sigma).cos();
+
+    let x = sin_u1 * sin_sigma - cos_u1 * cos_sigma * cos_alpha1;
+    let phi2 = (sin_u1 * cos_sigma + cos_u1 * sin_sigma * cos_alpha1)
+        .atan2((1. - F) * (sin_alpha * sin_alpha + x * x).sqrt());
+    let lambda = (sin_sigma * sin_alpha1).atan2(cos_u1 * cos_sigma - sin_u1 * sin_sigma * cos_alpha1);
+
+    let c = F / 16. * cos_sq_alpha * (4. + F * (4. - 3. * cos_sq_alpha));
+    let l = lambda - (1. - c) * F * sin_alpha *
+        (sigma + c * sin_sigma * (cos_2_sigma_m + c * cos_sigma * (-1. + 2. * cos_2_sigma_m * cos_2_sigma_m)));
+
+    let alpha2 = sin_alpha.atan2(-x);
+
+    Direct {
+        latitude: phi2.to_degrees(),
+        longitude: wrap_longitude(fix.longitude_f64() + l.to_degrees()),
+        final_bearing: to_bearing(alpha2),
+    }
+}
+
+/// Returns sine and cosine of the reduced latitude of `phi`
+fn reduced_latitude(phi: f64) -> (f64, f64) {
+    let tan_u = (1. - F) * phi.tan();
+    let cos_u = 1. / (1. + tan_u * tan_u).sqrt();
+    (tan_u * cos_u, cos_u)
+}
+
+/// Returns the coefficients `A` and `B` of the series expansion of the
+/// geodesic length
+fn series_coefficients(cos_sq_alpha: f64) -> (f64, f64) {
+    let u_sq = cos_sq_alpha * (A * A - B * B) / (B * B);
+    let a = 1. + u_sq / 16384. * (4096. + u_sq * (-768. + u_sq * (320. - 175. * u_sq)));
+    let b = u_sq / 1024. * (256. + u_sq * (-128. + u_sq * (74. - 47. * u_sq)));
+    (a, b)
+}
+
+fn sigma_correction(b: f64, sin_sigma: f64, cos_sigma: f64, cos_2_sigma_m: f64) -> f64 {
+    let cos_sq_2_sigma_m = cos_2_sigma_m * cos_2_sigma_m;
+    b * sin_sigma * (cos_2_sigma_m + b / 4. * (cos_sigma * (-1. + 2. * cos_sq_2_sigma_m) -
+        b / 6. * cos_2_sigma_m * (-3. + 4. * sin_sigma * sin_sigma) * (-3. + 4. * cos_sq_2_sigma_m)))
+}
+
+/// Converts an angle in radians to a bearing in degrees in the range `[0, 360)`
+fn to_bearing(angle: f64) -> f64 {
+    angle.to_degrees().rem_euclid(360.)
+}
@@ -1,18 +1,30 @@
 use crate::Point;
+use crate::flat::wrap_longitude;
+use crate::float::Float;
 
 pub fn haversine_distance(fix1: &dyn Point, fix2: &dyn Point) -> f32 {
-    const R: f32 = 6371.; // kilometres
+    haversine(fix1.latitude(), fix1.longitude(), fix2.latitude(), fix2.longitude())
+}
+
+/// Same as `haversine_distance()`, but in double precision
+pub fn haversine_distance_f64(fix1: &dyn Point, fix2: &dyn Point) -> f64 {
+    haversine(fix1.latitude_f64(), fix1.longitude_f64(), fix2.latitude_f64(), fix2.longitude_f64())
+}
+
+fn haversine<F: Float>(lat1: F, lon1: F, lat2: F, lon2: F) -> F {
+    let r = F::from_f64(6371.); // kilometres
+    let two = F::from_f64(2.);
 
-    let phi1 = fix1.latitude().to_radians();
-    let phi2 = fix2.latitude().to_radians();
-    let delta_phi = (fix2.latitude() - fix1.latitude()).to_radians();
-    let delta_rho = (fix2.longitude() - fix1.longitude()).to_radians();
+    let phi1 = lat1.to_radians();
+    let phi2 = lat2.to_radians();
+    let delta_phi = (lat2 - lat1).to_radians();
+    let delta_rho = wrap_longitude(lon2 - lon1).to_radians();
 
-    let a = (delta_phi / 2.).sin() * (delta_phi / 2.).sin() +
+    let a = (delta_phi / two).sin() * (delta_phi / two).sin() +
         phi1.cos() * phi2.cos() *
-            (delta_rho / 2.).sin() * (delta_rho / 2.).sin();
+            (delta_rho / two).sin() * (delta_rho / two).sin();
 
-    let c = 2. * a.sqrt().atan2((1. - a).sqrt());
+    let c = two * a.sqrt().atan2((F::one() - a).sqrt());
 
-    R * c
+    r * c
 }
@@ -0,0 +1,31 @@
+//! Heading changes along a track on the flat surface
+
+use flat_projection::FlatPoint;
+
+use crate::flat::wrap_longitude;
+
+/// Minimum distance in kilometers between two points to calculate a heading
+const MIN_HEADING_DISTANCE: f64 = 0.005;
+
+/// Returns the change of the heading in degrees at every point, i.e. between
+/// the leg to the point and the leg to the next point. Legs shorter than
+/// `MIN_HEADING_DISTANCE` have no heading and are skipped.
+///
+/// Right turns are positive and left turns are negative.
+pub(crate) fn turns(points: &[FlatPoint<f64>]) -> Vec<f64> {
+    let mut previous = None;
+    (0..points.len())
+        .map(|i| {
+            let next = match points.get(i + 1) {
+                Some(next) if points[i].distance(next) >= MIN_HEADING_DISTANCE => next,
+                _ => return 0.,
+            };
+
+            // heading changes wrap around at 180 degrees like longitudes
+            let heading = (next.x - points[i].x).atan2(next.y - points[i].y).to_degrees();
+            let turn = previous.map_or(0., |previous| wrap_longitude(heading - previous));
+            previous = Some(heading);
+            turn
+        })
+        .collect()
+}
@@ -0,0 +1,244 @@
+//! Reading of IGC files into fixes that can be passed to the optimizers
+//! (requires the `igc` feature)
+
+use std::io::Read;
+
+use ::igc::records::{BRecord, CRecordDeclaration, CRecordTurnpoint, FixValid, HRecord, IRecord};
+use failure::Error;
+use log::debug;
+
+use crate::Point;
+
+/// IGC files only contain two-digit years. Smaller years belong to the
+/// 21st century.
+const CENTURY_PIVOT: u16 = 80;
+
+/// Fix of an IGC file (B record)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    /// Time in seconds since midnight (UTC)
+    pub time: u32,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Pressure altitude in meters
+    pub altitude: i16,
+    /// GNSS altitude in meters
+    pub gnss_altitude: i16,
+    /// `false` if the logger reported a navigation warning (no 3D fix)
+    pub valid: bool,
+    /// Environmental noise level (`ENL` extension) from 0 to 999
+    pub enl: Option<u16>,
+    /// Means of propulsion sensor level (`MOP` extension) from 0 to 999
+    pub mop: Option<u16>,
+    /// Raw values of all extensions in the order of `Flight::extensions`.
+    /// Missing values are empty.
+    pub extensions: Vec<String>,
+}
+
+impl Point for Fix {
+    fn latitude(&self) -> f32 {
+        self.latitude as f32
+    }
+    fn longitude(&self) -> f32 {
+        self.longitude as f32
+    }
+    fn altitude(&self) -> i16 {
+        self.altitude
+    }
+    fn gnss_altitude(&self) -> Option<i16> {
+        Some(self.gnss_altitude)
+    }
+    fn time(&self) -> Option<u32> {
+        Some(self.time)
+    }
+    fn latitude_f64(&self) -> f64 {
+        self.latitude
+    }
+    fn longitude_f64(&self) -> f64 {
+        self.longitude
+    }
+    fn enl(&self) -> Option<u16> {
+        self.enl
+    }
+    fn mop(&self) -> Option<u16> {
+        self.mop
+    }
+}
+
+/// Calendar date (UTC)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Parses a date in the `DDMMYY` format of IGC files
+    fn parse(value: &str) -> Option<Date> {
+        let number = |range: std::ops::Range<usize>| value.get(range)?.parse::<u8>().ok();
+        let (day, month, year) = (number(0..2)?, number(2..4)?, u16::from(number(4..6)?));
+        if day == 0 || day > 31 || month == 0 || month > 12 {
+            return None;
+        }
+
+        let year = if year < CENTURY_PIVOT { 2000 + year } else { 1900 + year };
+        Some(Date { year, month, day })
+    }
+}
+
+/// Header of an IGC file (H records)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Header {
+    /// Date of the first fix (UTC)
+    pub date: Option<Date>,
+    pub pilot: Option<String>,
+    pub glider_type: Option<String>,
+    /// Registration of the glider
+    pub glider_id: Option<String>,
+    pub competition_id: Option<String>,
+    pub competition_class: Option<String>,
+    /// Type of the flight recorder
+    pub logger_type: Option<String>,
+    /// All H records as `(mnemonic, value)`, e.g. `("PLT", "Jane Doe")`
+    pub records: Vec<(String, String)>,
+}
+
+impl Header {
+    fn add(&mut self, record: &HRecord) {
+        let value = record.data.trim();
+        let text = Some(value.to_string()).filter(|value| !value.is_empty());
+
+        match record.mnemonic {
+            // newer loggers write e.g. `HFDTEDATE:180718,01`
+            "DTE" => self.date = Date::parse(value),
+            "PLT" => self.pilot = text,
+            "GTY" => self.glider_type = text,
+            "GID" => self.glider_id = text,
+            "CID" => self.competition_id = text,
+            "CCL" => self.competition_class = text,
+            "FTY" => self.logger_type = text,
+            _ => {}
+        }
+
+        self.records.push((record.mnemonic.to_string(), value.to_string()));
+    }
+}
+
+/// Task declaration of an IGC file (C records)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Task {
+    pub name: Option<String>,
+    pub takeoff: Option<TaskPoint>,
+    /// Start, turnpoints and finish
+    pub points: Vec<TaskPoint>,
+    pub landing: Option<TaskPoint>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub name: Option<String>,
+}
+
+impl Task {
+    /// Builds the task from the declaration and the following turnpoint
+    /// records, which start with the takeoff and end with the landing
+    fn new(declaration: &CRecordDeclaration, records: Vec<TaskPoint>) -> Self {
+        // takeoff and landing are often declared as `0000000N00000000E`
+        let declared = |point: Option<TaskPoint>| point.filter(|it| it.latitude != 0. || it.longitude != 0.);
+
+        let mut points = records;
+        let landing = if points.len() > 1 { declared(points.pop()) } else { None };
+        let takeoff = if !points.is_empty() { declared(Some(points.remove(0))) } else { None };
+
+        Task {
+            name: declaration.task_name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty()),
+            takeoff,
+            points,
+            landing,
+        }
+    }
+}
+
+/// Content of an IGC file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Flight {
+    pub header: Header,
+    /// Mnemonics of the B record extensions that are defined by the I
+    /// record, e.g. `ENL` or `MOP`
+    pub extensions: Vec<String>,
+    pub fixes: Vec<Fix>,
+    pub task: Option<Task>,
+}
+
+impl Flight {
+    /// Reads a whole IGC file. Invalid UTF-8 is replaced, e.g. in the
+    /// binary security records of some loggers.
+    pub fn read<R: Read>(mut reader: R) -> Result<Flight, Error> {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+        Ok(Flight::parse(&String::from_utf8_lossy(&content)))
+    }
+
+    /// Parses the content of an IGC file. Records that can not be parsed
+    /// are skipped.
+    pub fn parse(content: &str) -> Flight {
+        let mut flight = Flight::default();
+        let mut ranges = Vec::new();
+        let mut declaration = None;
+        let mut task_points = Vec::new();
+
+        for line in content.lines().map(|line| line.trim_end()) {
+            let result = match line.as_bytes().first() {
+                Some(b'B') => BRecord::parse(line).map(|record| flight.fixes.push(to_fix(&record, line, &flight.extensions, &ranges))),
+                Some(b'H') if line.is_char_boundary(5) => HRecord::parse(line).map(|record| flight.header.add(&record)),
+                Some(b'I') => IRecord::parse(line).map(|record| {
+                    flight.extensions = record.0.extensions.iter().map(|it| it.mnemonic.to_string()).collect();
+                    ranges = record.0.extensions.iter().map(|it| (usize::from(it.start_byte), usize::from(it.end_byte))).collect();
+                }),
+                // the turnpoint records have the N/S of the latitude at this position
+                Some(b'C') if matches!(line.as_bytes().get(8), Some(b'N') | Some(b'S')) => {
+                    CRecordTurnpoint::parse(line).map(|record| task_points.push(TaskPoint {
+                        latitude: record.position.lat.into(),
+                        longitude: record.position.lon.into(),
+                        name: record.turnpoint_name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty()),
+                    }))
+                }
+                Some(b'C') if declaration.is_none() => CRecordDeclaration::parse(line).map(|record| declaration = Some(record)),
+                _ => Ok(()),
+            };
+
+            if let Err(error) = result {
+                debug!("Skipping invalid record {:?}: {:?}", line, error);
+            }
+        }
+
+        flight.task = declaration.map(|declaration| Task::new(&declaration, task_points));
+        flight
+    }
+}
+
+fn to_fix(record: &BRecord, line: &str, extensions: &[String], ranges: &[(usize, usize)]) -> Fix {
+    // the byte positions of the extensions start at 1 and include the end
+    let values: Vec<String> = ranges.iter()
+        .map(|&(start, end)| line.get(start.saturating_sub(1)..end).unwrap_or("").to_string())
+        .collect();
+
+    let level = |mnemonic: &str| extensions.iter()
+        .position(|it| it == mnemonic)
+        .and_then(|i| values[i].parse().ok());
+
+    Fix {
+        time: record.timestamp.seconds_since_midnight(),
+        latitude: record.pos.lat.into(),
+        longitude: record.pos.lon.into(),
+        altitude: record.pressure_alt,
+        gnss_altitude: record.gps_alt,
+        valid: record.fix_valid == FixValid::Valid,
+        enl: level("ENL"),
+        mop: level("MOP"),
+        extensions: values,
+    }
+}
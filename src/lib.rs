@@ -1,7 +1,23 @@
+pub mod altitude;
+pub mod engine;
 pub mod olc;
+pub mod phases;
+pub mod out_and_return;
+pub mod outliers;
 pub mod flat;
+pub mod float;
+pub mod geodesic;
 pub mod haversine;
+#[cfg(feature = "igc")]
+pub mod igc;
+pub mod simplify;
+pub mod thermals;
+pub mod time;
+pub mod track;
+pub mod triangle;
+mod heading;
 mod point;
 mod parallel;
+mod tree;
 
 pub use crate::point::Point;
@@ -1,127 +1,711 @@
+use std::ops::Range;
+
 use failure::Error;
 use flat_projection::FlatPoint;
 use log::{debug, trace};
 use ord_subset::OrdVar;
 
 use crate::Point;
-use crate::flat::to_flat_points;
-use crate::haversine::haversine_distance;
+use crate::altitude::{altitudes, AltitudeSource};
+use crate::engine::{engine_runs, powerless_segments};
+use crate::flat::project_points;
+use crate::float::Float;
+use crate::geodesic;
+use crate::haversine::haversine_distance_f64;
 use crate::parallel::*;
+use crate::time::timeline;
+use crate::tree::BoxTree;
+
+pub(crate) use self::error::{check_route, check_track};
+use self::progress::Monitor;
+
+mod error;
+mod live;
+mod plus;
+mod progress;
+mod sprint;
 
+pub use self::error::OptimizeError;
+pub use self::live::LiveOptimizer;
+pub use self::plus::{plus, plus_with, PlusOptions, PlusResult};
+pub use self::progress::{CancellationToken, Cancelled, Progress};
+pub use self::sprint::{sprint, sprint_with, SprintOptions, SprintResult};
+
+/// Number of legs of the OLC classic free distance (start, five turnpoints, finish)
 const LEGS: usize = 6;
 
+/// Maximum height loss between start and finish in meters (OLC classic rules)
+const MAX_HEIGHT_LOSS: i16 = 1000;
+
+/// Number of points up to which the solution graph is calculated for the
+/// whole track right away. Longer tracks are downsampled to this number of
+/// points first and the results are used as lower bounds on the full track.
+const COARSE_POINTS: usize = 1000;
+
+/// Number of start altitudes for which additional solution graphs are
+/// calculated to get tighter upper bounds with the altitude rule
+const ALTITUDE_LEVELS: usize = 4;
+
+/// Number of consecutive points in a layer of the solution graph that are
+/// calculated together
+const LAYER_CHUNK_SIZE: usize = 256;
+
+/// Minimum distance in kilometers between the turnpoints of a path returned
+/// by `alternatives()` and the turnpoints of all better paths
+const ALTERNATIVE_SEPARATION: f64 = 5.;
+
 pub type Path = Vec<usize>;
 
+/// Rule for the altitude difference between start and finish
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AltitudeRule {
+    /// Paths that lose more than the given height in meters between start
+    /// and finish are invalid
+    MaxHeightLoss(i16),
+    /// Paths that lose more than `max_height_loss` meters between start and
+    /// finish are penalized by `penalty_per_meter` kilometers for every meter
+    /// of additional height loss
+    Penalty { max_height_loss: i16, penalty_per_meter: f64 },
+    /// The altitudes of start and finish are not checked
+    Disabled,
+}
+
+impl AltitudeRule {
+    /// Returns the distance penalty in kilometers for the given height loss
+    /// in meters, or `None` if the path is invalid
+    fn penalty(&self, height_loss: i32) -> Option<f64> {
+        match *self {
+            AltitudeRule::MaxHeightLoss(max_height_loss) => {
+                if height_loss <= i32::from(max_height_loss) { Some(0.) } else { None }
+            }
+            AltitudeRule::Penalty { max_height_loss, penalty_per_meter } => {
+                let excess = (height_loss - i32::from(max_height_loss)).max(0);
+                Some(f64::from(excess) * penalty_per_meter)
+            }
+            AltitudeRule::Disabled => Some(0.),
+        }
+    }
+}
+
+/// Formula for the distance of the resulting path. The search itself always
+/// runs on the flat projection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceFormula {
+    /// Great-circle distance on a sphere (`haversine::haversine_distance_f64()`)
+    Haversine,
+    /// Geodesic distance on the WGS84 ellipsoid (`geodesic::distance()`), as
+    /// required by the FAI Sporting Code
+    Wgs84,
+}
+
+impl DistanceFormula {
+    /// Distance in kilometers between two fixes in double precision
+    pub(crate) fn distance(self, fix1: &dyn Point, fix2: &dyn Point) -> f64 {
+        match self {
+            DistanceFormula::Haversine => haversine_distance_f64(fix1, fix2),
+            DistanceFormula::Wgs84 => geodesic::distance(fix1, fix2),
+        }
+    }
+}
+
+/// Floating point precision of the flat projection and the search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    /// `f32`, which is faster but only resolves about one meter
+    Single,
+    /// `f64`, e.g. for record claims
+    Double,
+}
+
+/// Rule for the fixes that were logged while the engine was running, see
+/// `engine::engine_runs()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineRule {
+    /// Engine runs are not detected and all fixes are scored
+    Ignore,
+    /// Only the fixes after the last engine run are scored
+    AfterLastRun,
+    /// Start and finish have to be within the same segment between two
+    /// engine runs
+    PowerlessSegments,
+}
+
+#[derive(Debug, Clone)]
+pub struct OptimizeOptions {
+    /// Number of legs between start and finish. The resulting path will
+    /// contain `legs + 1` points.
+    pub legs: usize,
+    pub altitude_rule: AltitudeRule,
+    /// Altitudes that are used for the `altitude_rule`
+    pub altitude_source: AltitudeSource,
+    /// Formula for the `distance` of the result
+    pub distance_formula: DistanceFormula,
+    pub precision: Precision,
+    /// Not supported by the `LiveOptimizer`
+    pub engine_rule: EngineRule,
+    /// Indices of the fixes that can not be used as start, turnpoint or
+    /// finish, e.g. from `outliers::find_outliers()`. Not supported by the
+    /// `LiveOptimizer`.
+    pub ineligible_fixes: Vec<usize>,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            legs: LEGS,
+            altitude_rule: AltitudeRule::MaxHeightLoss(MAX_HEIGHT_LOSS),
+            altitude_source: AltitudeSource::Auto,
+            distance_formula: DistanceFormula::Haversine,
+            precision: Precision::Single,
+            engine_rule: EngineRule::Ignore,
+            ineligible_fixes: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct OptimizationResult {
     pub path: Path,
-    pub distance: f32,
+    /// Scored distance in kilometers, i.e. the sum of all legs minus the
+    /// `penalty`, calculated in double precision
+    pub distance: f64,
+    /// Altitude rule that was applied to the path
+    pub altitude_rule: AltitudeRule,
+    /// Altitudes that were used for the `altitude_rule`
+    pub altitude_source: AltitudeSource,
+    /// Start altitude minus finish altitude in meters
+    pub height_loss: i32,
+    /// Distance penalty in kilometers caused by the `altitude_rule`
+    pub penalty: f64,
+    /// Time of the start fix in seconds since midnight, if the fixes have times
+    pub start_time: Option<u32>,
+    /// Time of the finish fix in seconds since midnight of the start day,
+    /// if the fixes have times
+    pub finish_time: Option<u32>,
 }
 
+impl OptimizationResult {
+    /// Time between start and finish in seconds, if the fixes have times
+    pub fn duration(&self) -> Option<u32> {
+        Some(self.finish_time? - self.start_time?)
+    }
+}
+
+/// Best path found in a solution graph with its distance on the flat surface
+#[derive(Debug, Clone)]
+struct Solution<F> {
+    path: Path,
+    distance: F,
+}
+
+/// Finds the longest valid path through the `route` under the OLC classic
+/// rules.
+///
+/// Fixes that are not later than the fixes before them, e.g. duplicate B
+/// records, are skipped. Use `track::sanitize()` first to keep out-of-order
+/// fixes instead.
 pub fn optimize<T: Point>(route: &[T]) -> Result<OptimizationResult, Error> {
+    optimize_with(route, &OptimizeOptions::default())
+}
+
+pub fn optimize_with<T: Point>(route: &[T], options: &OptimizeOptions) -> Result<OptimizationResult, Error> {
+    search(route, options, &mut Monitor::none())
+}
+
+/// Same as `optimize_with()`, but calls `progress` regularly while the
+/// optimization is running and stops it as soon as the `token` is cancelled.
+/// In that case a `Cancelled` error with the best valid path that was found
+/// so far is returned.
+pub fn optimize_with_progress<T, F>(
+    route: &[T],
+    options: &OptimizeOptions,
+    token: &CancellationToken,
+    mut progress: F,
+) -> Result<OptimizationResult, Error>
+    where T: Point, F: FnMut(&Progress)
+{
+    search(route, options, &mut Monitor::new(token, &mut progress))
+}
+
+/// Finds up to `count` valid paths with different turnpoints, ordered by
+/// their distance. The first result is the same as for `optimize_with()`,
+/// every following one is the best valid path that has no turnpoint within
+/// `ALTERNATIVE_SEPARATION` of a turnpoint of the paths before it.
+pub fn alternatives<T>(route: &[T], options: &OptimizeOptions, count: usize) -> Result<Vec<OptimizationResult>, Error>
+    where T: Point
+{
+    if count == 0 {
+        return Err(OptimizeError::InvalidCount.into());
+    }
+
+    let mut options = options.clone();
+    let mut results = Vec::new();
+    while results.len() < count {
+        let result = match search(route, &options, &mut Monitor::none()) {
+            Ok(result) => result,
+            // there are no more paths that are far enough from the others
+            Err(_) if !results.is_empty() => break,
+            Err(error) => return Err(error),
+        };
+
+        let (turnpoints, formula) = (&result.path[1..result.path.len() - 1], options.distance_formula);
+        options.ineligible_fixes.extend((0..route.len()).filter(|&index| {
+            turnpoints.iter().any(|&turnpoint| formula.distance(&route[index], &route[turnpoint]) < ALTERNATIVE_SEPARATION)
+        }));
+
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+/// Finds the best valid path. Returns a `Cancelled` error with the best
+/// valid path that was found so far if the `monitor` is cancelled.
+fn search<T: Point>(route: &[T], options: &OptimizeOptions, monitor: &mut Monitor) -> Result<OptimizationResult, Error> {
+    let kept = check_route(route, options.legs)?;
+    let fixes: Vec<&T> = kept.iter().map(|&index| &route[index]).collect();
+
+    let (altitude_source, altitudes) = route_altitudes(&fixes, options)?;
+
+    // the ineligible fixes refer to the complete `route`
+    let mut eligible = vec![true; route.len()];
+    for &index in options.ineligible_fixes.iter().filter(|&&index| index < route.len()) {
+        eligible[index] = false;
+    }
+    let eligible: Vec<_> = kept.iter().map(|&index| eligible[index]).collect();
+
+    let segments: Vec<_> = scored_segments(&fixes, options)?.into_iter()
+        .map(|segment| {
+            let indices: Vec<_> = segment.clone()
+                .filter(|&index| eligible[index])
+                .map(|index| index - segment.start)
+                .collect();
+            (segment, indices)
+        })
+        .filter(|(_, indices)| indices.len() > options.legs)
+        .collect();
+
+    if segments.is_empty() {
+        return Err(match options.engine_rule {
+            EngineRule::Ignore => {
+                let fixes = eligible.iter().filter(|&&eligible| eligible).count();
+                OptimizeError::TooFewFixes { fixes, required: options.legs + 1 }
+            }
+            EngineRule::AfterLastRun | EngineRule::PowerlessSegments => OptimizeError::NoPowerlessSegment,
+        }.into());
+    }
+
+    let mut paths = Vec::new();
+    for (segment, indices) in segments {
+        let (fixes, altitudes) = (&fixes[segment.clone()], &altitudes[segment.clone()]);
+        let segment_path = match options.precision {
+            Precision::Single => search_path::<f32, &T>(fixes, altitudes, &indices, options, monitor),
+            Precision::Double => search_path::<f64, &T>(fixes, altitudes, &indices, options, monitor),
+        };
+
+        paths.extend(segment_path.map(|path| path.into_iter().map(|index| index + segment.start).collect::<Path>()));
+    }
+
+    let times = timeline(&fixes);
+    let best = paths.into_iter()
+        .map(|path| to_result(&fixes, &altitudes, times.as_deref(), options, altitude_source, path))
+        .max_by_key(|result| OrdVar::new_checked(result.distance))
+        .map(|result| OptimizationResult { path: result.path.iter().map(|&index| kept[index]).collect(), ..result });
+
+    // the last steps of the search may already have been running when the
+    // token was cancelled
+    if monitor.is_cancelled() {
+        return Err(Cancelled { best }.into());
+    }
+
+    best.ok_or_else(|| OptimizeError::NoValidSolution.into())
+}
+
+/// Returns the index ranges of the `route` that can be scored under the
+/// engine rule
+fn scored_segments<T: Point>(route: &[T], options: &OptimizeOptions) -> Result<Vec<Range<usize>>, OptimizeError> {
+    let runs = match options.engine_rule {
+        EngineRule::Ignore => Vec::new(),
+        EngineRule::AfterLastRun | EngineRule::PowerlessSegments => {
+            let (source, runs) = engine_runs(route).ok_or(OptimizeError::MissingTimes)?;
+            debug!("Detected {} engine runs from {:?}", runs.len(), source);
+            runs
+        }
+    };
+
+    let first_index = match (options.engine_rule, runs.last()) {
+        (EngineRule::AfterLastRun, Some(last)) => last.end,
+        _ => 0,
+    };
+
+    Ok(powerless_segments(route.len(), &runs).into_iter()
+        .filter(|it| it.start >= first_index)
+        .collect())
+}
+
+/// Finds the best valid path through the points at the given `indices` of
+/// the `route` with the precision of `F`
+fn search_path<F: Float, T: Point>(
+    route: &[T],
+    altitudes: &[i16],
+    indices: &[usize],
+    options: &OptimizeOptions,
+    monitor: &mut Monitor,
+) -> Option<Path> {
     debug!("Converting {} points to flat points", route.len());
-    let flat_points = to_flat_points(route);
+    let flat_points = project_points::<F, T>(route)?;
+
+    // the search only sees the eligible points
+    let (flat_points, altitudes): (Vec<_>, Vec<_>) = indices.iter()
+        .map(|&index| (flat_points[index], altitudes[index]))
+        .unzip();
 
-    debug!("Calculating distance matrix (finish -> start)");
-    let dist_matrix = full_dist_matrix(&flat_points);
+    let problem = Problem { flat_points: &flat_points, altitudes: &altitudes, options, first_finish: 0 };
+    find_solution(&problem, monitor)
+        .map(|solution| solution.path.into_iter().map(|index| indices[index]).collect())
+}
+
+/// Returns the altitudes that are used for the altitude rule
+fn route_altitudes<T>(route: &[T], options: &OptimizeOptions) -> Result<(AltitudeSource, Vec<i16>), OptimizeError>
+    where T: Point
+{
+    let (altitude_source, altitudes) = altitudes(route, options.altitude_source)
+        .ok_or(OptimizeError::MissingGnssAltitude)?;
+    debug!("Using {:?} altitudes", altitude_source);
+
+    Ok((altitude_source, altitudes))
+}
+
+/// Points and rules that are shared by the solution graphs of a search on
+/// the flat surface
+struct Problem<'a, F> {
+    flat_points: &'a [FlatPoint<F>],
+    altitudes: &'a [i16],
+    options: &'a OptimizeOptions,
+    /// Index of the first point at which the paths can finish
+    first_finish: usize,
+}
+
+/// Finds the best valid path on the flat surface
+fn find_solution<F: Float>(problem: &Problem<'_, F>, monitor: &mut Monitor) -> Option<Solution<F>> {
+    if problem.flat_points.len() <= COARSE_POINTS {
+        return solve_graph(problem, None, monitor);
+    }
+
+    let coarse = coarse_solution(problem, monitor);
+    if let Some(solution) = &coarse {
+        debug!("Coarse solution: {:.3} km -> {:?}", solution.distance, solution.path);
+    }
+
+    debug!("Refining solutions on {} points", problem.flat_points.len());
+    solve_graph(problem, coarse, monitor)
+}
+
+/// Converts a `path` on the flat surface into the result for the `route`
+fn to_result<T: Point>(
+    route: &[T],
+    altitudes: &[i16],
+    times: Option<&[u32]>,
+    options: &OptimizeOptions,
+    altitude_source: AltitudeSource,
+    path: Path,
+) -> OptimizationResult {
+    let height_loss = height_loss(altitudes, &path);
+    let penalty = options.altitude_rule.penalty(height_loss).unwrap_or(0.);
+    let distance = calculate_distance(route, &path, options.distance_formula) - penalty;
+    debug!("Solution: {:?} ({:.3} km)", path, distance);
+
+    let start_time = times.map(|times| times[path[0]]);
+    let finish_time = times.map(|times| times[path[path.len() - 1]]);
+
+    OptimizationResult {
+        distance,
+        path,
+        altitude_rule: options.altitude_rule,
+        altitude_source,
+        height_loss,
+        penalty,
+        start_time,
+        finish_time,
+    }
+}
+
+/// Returns the penalty of the `altitude_rule` with the precision of the flat
+/// surface
+fn flat_penalty<F: Float>(altitude_rule: &AltitudeRule, height_loss: i32) -> Option<F> {
+    altitude_rule.penalty(height_loss).map(|penalty| F::from_f64(penalty))
+}
+
+/// Finds the best valid path, starting from the `best` known one.
+///
+/// The solution graph for all points gives an upper bound for the paths of
+/// every start point. A separate solution graph is then calculated for every
+/// start point that could still lead to a better valid path. The search
+/// stops early if the optimization is cancelled.
+fn solve_graph<F>(problem: &Problem<'_, F>, mut best: Option<Solution<F>>, monitor: &mut Monitor) -> Option<Solution<F>>
+    where F: Float
+{
+    let Problem { flat_points, altitudes, options, first_finish } = *problem;
+    let (altitude_rule, legs) = (&options.altitude_rule, options.legs);
+    if monitor.is_cancelled() {
+        debug!("Optimization was cancelled");
+        return best;
+    }
+
+    debug!("Building bounding box tree");
+    let tree = BoxTree::new(flat_points);
 
     debug!("Calculating solution graph");
-    let graph = Graph::from_distance_matrix(&dist_matrix);
+    let finish_values: Vec<_> = (0..flat_points.len())
+        .map(|index| if index < first_finish { F::neg_infinity() } else { F::zero() })
+        .collect();
+    let graph = Graph::from_flat_points(&tree, flat_points, legs, 0, &finish_values, monitor);
 
     debug!("Searching for best valid solution");
-    let mut best_valid = graph.find_best_valid_solution(&route);
-    debug!("-- New best solution: {:.3} km -> {:?}", calculate_distance(route, &best_valid.path), best_valid.path);
+    if let Some(best_valid) = graph.find_best_valid_solution(altitudes, altitude_rule) {
+        debug!("-- New best solution: {:.3} km -> {:?}", best_valid.distance, best_valid.path);
+        improve_solution(&mut best, best_valid);
+    }
 
     debug!("Searching for potentially better solutions");
-    let mut start_candidates: Vec<_> = graph.g[LEGS - 1].iter()
+    let mut start_candidates: Vec<_> = graph.g[legs - 1].iter()
         .enumerate()
-        .filter(|(_, cell)| cell.distance > best_valid.distance)
-        .map(|(start_index, cell)| StartCandidate { distance: cell.distance, start_index })
+        .filter(|(_, cell)| cell.distance > min_distance(&best))
+        .map(|(start_index, cell)| StartCandidate { distance: cell.distance, start_index, path: None })
         .collect();
 
+    if *altitude_rule != AltitudeRule::Disabled {
+        let mut levels = Vec::new();
+        while start_candidates.len() > ALTITUDE_LEVELS && !monitor.is_cancelled() {
+            debug!("Calculating upper bounds with the altitude rule for {} start points", start_candidates.len());
+            if !tighten_upper_bounds(problem, &tree, &mut levels, &mut start_candidates, monitor) {
+                break;
+            }
+
+            // the upper bounds of some start points are reached by valid paths already
+            for candidate in &mut start_candidates {
+                if let Some(path) = candidate.path.take() {
+                    improve_solution(&mut best, Solution { path, distance: candidate.distance });
+                    candidate.distance = F::neg_infinity();
+                }
+            }
+
+            let min_distance = min_distance(&best);
+            start_candidates.retain(|it| it.distance > min_distance);
+        }
+    }
+
     start_candidates.sort_by_key(|it| OrdVar::new_checked(it.distance));
     debug!("{} potentially better start points found", start_candidates.len());
 
+    let mut candidate_number = 0;
     while let Some(candidate) = start_candidates.pop() {
+        if monitor.is_cancelled() {
+            debug!("Optimization was cancelled");
+            break;
+        }
+
+        candidate_number += 1;
+        monitor.report(Progress::StartCandidate {
+            candidate: candidate_number,
+            candidates: candidate_number + start_candidates.len(),
+            best_distance: best.as_ref().map_or(0., |it| it.distance.into_f64()),
+        });
+
         debug!("Calculating solution graph with start point at index {}", candidate.start_index);
-        let candidate_graph = Graph::for_start_index(candidate.start_index, &dist_matrix);
+        let mut candidate_graph = Graph::for_start_index(&tree, candidate.start_index, flat_points, legs);
+
+        // exclude the finish points before `first_finish`, the finish layer
+        // starts at the start point
+        let finishes = &mut candidate_graph.g[legs - 1];
+        let excluded = first_finish.saturating_sub(candidate.start_index).min(finishes.len());
+        finishes[..excluded].iter_mut().for_each(|cell| cell.distance = F::neg_infinity());
+
+        let best_valid_for_candidate = candidate_graph.find_best_valid_solution(altitudes, altitude_rule);
+        let best_valid_for_candidate = match best_valid_for_candidate {
+            Some(solution) => solution,
+            None => continue,
+        };
 
-        let best_valid_for_candidate = candidate_graph.find_best_valid_solution(&route);
-        if best_valid_for_candidate.distance > best_valid.distance {
-            best_valid = best_valid_for_candidate;
-            debug!("-- New best solution: {:.3} km -> {:?}", calculate_distance(route, &best_valid.path), best_valid.path);
+        let distance = best_valid_for_candidate.distance;
+        if improve_solution(&mut best, best_valid_for_candidate) {
+            debug!("-- New best solution: {:.3} km", distance);
 
-            start_candidates.retain(|it| it.distance > best_valid.distance);
+            let min_distance = min_distance(&best);
+            start_candidates.retain(|it| it.distance > min_distance);
         } else {
-            debug!("Discarding solution with {:.3} km", calculate_distance(route, &best_valid_for_candidate.path));
+            debug!("Discarding solution with {:.3} km", distance);
         }
 
         debug!("{} potentially better start points left", start_candidates.len());
     }
 
-    let distance = calculate_distance(route, &best_valid.path);
-    debug!("Solution: {:?} ({:.3} km)", best_valid.path, distance);
+    best
+}
+
+/// Tightens the upper bounds of the `start_candidates` by calculating
+/// additional solution graphs that include the `altitude_rule` for a few
+/// start altitudes, which are added to the already used `levels`. Returns
+/// `false` if there are no new start altitudes.
+fn tighten_upper_bounds<F: Float>(
+    problem: &Problem<'_, F>,
+    tree: &BoxTree<F>,
+    levels: &mut Vec<i16>,
+    start_candidates: &mut [StartCandidate<F>],
+    monitor: &mut Monitor,
+) -> bool {
+    let Problem { flat_points, altitudes, options, first_finish } = *problem;
+    let mut start_altitudes: Vec<_> = start_candidates.iter().map(|it| altitudes[it.start_index]).collect();
+    start_altitudes.sort_unstable();
+
+    let mut new_levels: Vec<_> = (0..ALTITUDE_LEVELS)
+        .map(|i| start_altitudes[i * start_altitudes.len() / ALTITUDE_LEVELS])
+        .filter(|level| !levels.contains(level))
+        .collect();
+    new_levels.dedup();
+
+    let first_index = start_candidates.iter().map(|it| it.start_index).min().unwrap_or(0);
+
+    for (level_index, &level) in new_levels.iter().enumerate() {
+        trace!("-- Analyzing start altitude {} m", level);
+        monitor.report(Progress::UpperBounds {
+            level: level_index + 1,
+            levels: new_levels.len(),
+            candidates: start_candidates.len(),
+        });
+
+        // every start point at or above `level` loses at least as much height
+        // as a start point at `level`
+        let penalties: Vec<_> = altitudes.iter()
+            .map(|&altitude| flat_penalty::<F>(&options.altitude_rule, i32::from(level) - i32::from(altitude)))
+            .collect();
 
-    Ok(OptimizationResult { distance, path: best_valid.path })
+        let finish_values: Vec<_> = penalties.iter()
+            .enumerate()
+            .map(|(index, penalty)| match penalty {
+                Some(penalty) if index >= first_finish => -*penalty,
+                _ => F::neg_infinity(),
+            })
+            .collect();
+        let graph = Graph::from_flat_points(tree, flat_points, options.legs, first_index, &finish_values, monitor);
+        let start_layer = &graph.g[options.legs - 1];
+
+        for candidate in start_candidates.iter_mut().filter(|it| altitudes[it.start_index] >= level) {
+            let distance = start_layer[candidate.start_index - first_index].distance;
+            if distance < candidate.distance {
+                candidate.distance = distance;
+
+                // the path is the best one for this start point if its
+                // penalty is the same as for a start point at `level`
+                candidate.path = Some(distance)
+                    .filter(|&distance| distance > F::neg_infinity())
+                    .map(|_| graph.path(candidate.start_index))
+                    .filter(|path| {
+                        let penalty = flat_penalty(&options.altitude_rule, height_loss(altitudes, path));
+                        penalty.is_some() && penalty == penalties[path[path.len() - 1]]
+                    });
+            }
+        }
+    }
+
+    levels.extend(new_levels.iter());
+    !new_levels.is_empty()
 }
 
-#[derive(Debug)]
-struct StartCandidate {
-    distance: f32,
-    start_index: usize,
+/// Finds the best valid path on a downsampled track with at most
+/// `COARSE_POINTS` points and returns it with indices of the full track
+fn coarse_solution<F: Float>(problem: &Problem<'_, F>, monitor: &mut Monitor) -> Option<Solution<F>> {
+    let (flat_points, altitudes) = (problem.flat_points, problem.altitudes);
+    let stride = flat_points.len().div_ceil(COARSE_POINTS);
+    let mut indices: Vec<usize> = (0..flat_points.len()).step_by(stride).collect();
+    if indices.last() != Some(&(flat_points.len() - 1)) {
+        indices.push(flat_points.len() - 1);
+    }
+
+    debug!("Downsampling to {} points", indices.len());
+    let coarse_points: Vec<_> = indices.iter().map(|&i| flat_points[i]).collect();
+    let coarse_altitudes: Vec<_> = indices.iter().map(|&i| altitudes[i]).collect();
+
+    let first_finish = indices.partition_point(|&i| i < problem.first_finish);
+    let coarse = Problem { flat_points: &coarse_points, altitudes: &coarse_altitudes, first_finish, ..*problem };
+    solve_graph(&coarse, None, monitor)
+        .map(|solution| Solution {
+            path: solution.path.iter().map(|&i| indices[i]).collect(),
+            distance: solution.distance,
+        })
 }
 
-/// Generates a N*N matrix with the distances in kilometers between all points.
-fn full_dist_matrix(flat_points: &[FlatPoint<f32>]) -> Vec<Vec<f32>> {
-    opt_par_iter(flat_points)
-        .map(|p1| flat_points.iter()
-            .map(|p2| p1.distance(p2))
-            .collect())
-        .collect()
+/// Replaces the `best` solution if the `solution` is longer. Returns `false`
+/// if the `solution` was discarded.
+fn improve_solution<F: Float>(best: &mut Option<Solution<F>>, solution: Solution<F>) -> bool {
+    if best.as_ref().is_some_and(|best| solution.distance <= best.distance) {
+        return false;
+    }
+
+    *best = Some(solution);
+    true
 }
 
-struct Graph {
-    g: Vec<Vec<GraphCell>>,
+/// Returns the distance that a new solution has to exceed to replace the
+/// `best` one
+fn min_distance<F: Float>(best: &Option<Solution<F>>) -> F {
+    best.as_ref().map_or(F::neg_infinity(), |best| best.distance)
 }
 
 #[derive(Debug)]
-struct GraphCell {
-    prev_index: usize,
-    distance: f32,
+struct StartCandidate<F> {
+    /// Upper bound for the distance of all valid paths from this start point
+    distance: F,
+    start_index: usize,
+    /// Valid path that reaches the upper bound, if known. This path is the
+    /// best one for this start point.
+    path: Option<Path>,
 }
 
-impl Graph {
-    fn from_distance_matrix(dist_matrix: &[Vec<f32>]) -> Self {
-        let mut graph: Vec<Vec<GraphCell>> = Vec::with_capacity(LEGS);
-
-        // layer: 0 / leg: 6
-        //
-        // assuming X is the fifth turnpoint, what is the furthest away finish point
-        trace!("-- Analyzing leg #{}", 6);
-
-        let layer = opt_par_iter(dist_matrix)
-            .enumerate()
-            .map(|(tp_index, distances)| distances.iter()
-                .enumerate()
-                .skip(tp_index)
-                .map(|(finish_index, &distance)| GraphCell { prev_index: finish_index, distance })
-                .max_by_key(|cell| OrdVar::new_checked(cell.distance))
-                .unwrap())
-            .collect();
-
-        graph.push(layer);
+struct Graph<F> {
+    g: Vec<Vec<GraphCell<F>>>,
+    /// Index of the point that the layers start with
+    offset: usize,
+    /// `true` if the last layer contains the finish points instead of the
+    /// start points
+    reversed: bool,
+}
 
-        for layer_index in 1..LEGS {
-            trace!("-- Analyzing leg #{}", LEGS - layer_index);
+#[derive(Debug)]
+struct GraphCell<F> {
+    prev_index: usize,
+    distance: F,
+}
 
-            // layer: 1 / leg: 5
+impl<F: Float> Graph<F> {
+    /// Builds the solution graph for all start points at or after
+    /// `first_index`. The value in `finish_values` is added to every path
+    /// that finishes at the respective point, so that finish points can be
+    /// excluded via negative infinity.
+    fn from_flat_points(
+        tree: &BoxTree<F>,
+        flat_points: &[FlatPoint<F>],
+        legs: usize,
+        first_index: usize,
+        finish_values: &[F],
+        monitor: &mut Monitor,
+    ) -> Self {
+        let mut graph: Vec<Vec<GraphCell<F>>> = Vec::with_capacity(legs);
+
+        // points before first_index can not be used
+        let mut values = vec![F::neg_infinity(); flat_points.len()];
+        values[first_index..].copy_from_slice(&finish_values[first_index..]);
+
+        for layer_index in 0..legs {
+            trace!("-- Analyzing leg #{}", legs - layer_index);
+
+            // layer: 0 / leg: 6
             //
-            // assuming X is the fourth turnpoint, what is the fifth turnpoint
-            // that results in the highest total distance?
+            // assuming X is the fifth turnpoint, what is the furthest away finish point
             //
-            // layer: 2 / leg: 4
+            // layer: 1 / leg: 5
             //
-            // assuming X is the third turnpoint, what is the fourth turnpoint
+            // assuming X is the fourth turnpoint, what is the fifth turnpoint
             // that results in the highest total distance?
             //
             // ...
@@ -131,47 +715,44 @@ impl Graph {
             // assuming X is the start point, what is the first turnpoint
             // that results in the highest total distance?
 
-            let last_layer = &graph[layer_index - 1];
-
-            let layer = opt_par_iter(dist_matrix)
-                .enumerate()
-                .map(|(tp_index, distances)| {
-                    distances.iter()
-                        .zip(last_layer.iter())
-                        .enumerate()
-                        .skip(tp_index)
-                        .map(|(prev_index, (&leg_dist, last_layer_cell))| {
-                            let distance = last_layer_cell.distance + leg_dist;
-                            GraphCell { prev_index, distance }
-                        })
-                        .max_by_key(|cell| OrdVar::new_checked(cell.distance))
-                        .unwrap()
-                })
-                .collect();
+            let range = |tp_index| tp_index + 1..flat_points.len();
+            let layer = solve_layer(tree, flat_points, &values, first_index, range);
+            for (value, cell) in values[first_index..].iter_mut().zip(layer.iter()) {
+                *value = cell.distance;
+            }
 
             graph.push(layer);
+            monitor.report(Progress::SolutionGraph { leg: layer_index + 1, legs });
         }
 
-        Graph { g: graph }
+        Graph { g: graph, offset: first_index, reversed: false }
     }
 
-    fn for_start_index(start_index: usize, dist_matrix: &[Vec<f32>]) -> Self {
-        let mut graph: Vec<Vec<GraphCell>> = Vec::with_capacity(LEGS);
+    fn for_start_index(tree: &BoxTree<F>, start_index: usize, flat_points: &[FlatPoint<F>], legs: usize) -> Self {
+        let mut graph: Vec<Vec<GraphCell<F>>> = Vec::with_capacity(legs);
 
         trace!("-- Analyzing leg #{}", 1);
 
         // layer: 0 / leg: 1
         //
         // assuming X is the first turnpoint, what is the distance to `start_index`?
-        let layer = dist_matrix[start_index].iter()
+        let start = &flat_points[start_index];
+        let layer: Vec<_> = flat_points.iter()
+            .enumerate()
             // skip points before start_index
             .skip(start_index)
-            .map(|&distance| GraphCell { prev_index: start_index, distance })
+            .map(|(tp_index, tp)| {
+                let distance = if tp_index > start_index { start.distance(tp) } else { F::neg_infinity() };
+                GraphCell { prev_index: start_index, distance }
+            })
             .collect();
 
+        // points before start_index can not be used
+        let mut values = vec![F::neg_infinity(); flat_points.len()];
+
         graph.push(layer);
 
-        for layer_index in 1..LEGS {
+        for layer_index in 1..legs {
             trace!("-- Analyzing leg #{}", layer_index + 1);
 
             // layer: 1 / leg: 2
@@ -179,11 +760,6 @@ impl Graph {
             // assuming X is the second turnpoint, what is the first turnpoint
             // that results in the highest total distance?
             //
-            // layer: 2 / leg: 3
-            //
-            // assuming X is the third turnpoint, what is the second turnpoint
-            // that results in the highest total distance?
-            //
             // ...
             //
             // layer: 5 / leg: 6
@@ -191,84 +767,99 @@ impl Graph {
             // assuming X is the finish point, what is the fifth turnpoint
             // that results in the highest total distance?
 
-            let last_layer = &graph[layer_index - 1];
-
-            let layer = opt_par_iter(dist_matrix)
-                // skip points before start_index
-                .skip(start_index)
-                .enumerate()
-                .map(|(tp_index, distances)| {
-                    distances.iter()
-                        .skip(start_index)
-                        .zip(last_layer.iter())
-                        .enumerate()
-                        .take(tp_index + 1)
-                        .map(|(prev_index, (&leg_dist, last_layer_cell))| {
-                            let prev_index = prev_index + start_index;
-                            let distance = last_layer_cell.distance + leg_dist;
-                            GraphCell { prev_index, distance }
-                        })
-                        .max_by_key(|cell| OrdVar::new_checked(cell.distance))
-                        .unwrap()
-                })
-                .collect();
+            for (value, cell) in values[start_index..].iter_mut().zip(graph[layer_index - 1].iter()) {
+                *value = cell.distance;
+            }
 
+            let layer = solve_layer(tree, flat_points, &values, start_index, |tp_index| start_index..tp_index);
             graph.push(layer);
         }
 
-        Graph { g: graph }
+        Graph { g: graph, offset: start_index, reversed: true }
     }
 
-    /// Finds the best (largest distance), valid (with 1000m rule) path
-    /// through the graph and returns `(distance, path)`
-    fn find_best_valid_solution<T: Point>(&self, points: &[T]) -> OptimizationResult {
-        let last_graph_row = self.g.last().unwrap();
+    /// Returns the path that ends at `index` in the last layer of the graph
+    fn path(&self, index: usize) -> Path {
+        let iter = GraphIterator {
+            graph: self,
+            next: Some((self.g.len(), index)),
+            offset: self.offset,
+        };
 
-        let offset = points.len() - last_graph_row.len();
+        let mut path = iter.collect::<Vec<_>>();
+        if self.reversed {
+            path.reverse();
+        }
+
+        debug_assert!(path.windows(2).all(|pair| pair[0] < pair[1]), "path is not strictly increasing: {:?}", path);
+        path
+    }
 
-        last_graph_row.iter()
+    /// Finds the best (largest distance), valid (with `altitude_rule`) path
+    /// through the graph
+    fn find_best_valid_solution(&self, altitudes: &[i16], altitude_rule: &AltitudeRule) -> Option<Solution<F>> {
+        self.g.last().unwrap().iter()
             .enumerate()
+            .filter(|(_, cell)| cell.distance > F::neg_infinity())
             .filter_map(|(index, cell)| {
-                let iter = GraphIterator {
-                    graph: self,
-                    next: Some((self.g.len(), index + offset)),
-                    offset,
-                };
-
-                let mut path = iter.collect::<Vec<_>>();
-                if *path.first().unwrap() > *path.last().unwrap() {
-                    path.reverse();
-                }
-
-                let start_index = *path.first().unwrap();
-                let finish_index = *path.last().unwrap();
-                let start = &points[start_index];
-                let finish = &points[finish_index];
-                let altitude_delta = start.altitude() - finish.altitude();
-                if altitude_delta <= 1000  {
-                    Some(OptimizationResult { distance: cell.distance, path })
-                } else {
-                    None
-                }
+                let path = self.path(index + self.offset);
+                flat_penalty(altitude_rule, height_loss(altitudes, &path))
+                    .map(|penalty| Solution { distance: cell.distance - penalty, path })
             })
             .max_by_key(|result| OrdVar::new_checked(result.distance))
-            .unwrap()
     }
 }
 
-struct GraphIterator<'a> {
-    graph: &'a Graph,
+/// Calculates a layer of the solution graph for the points starting at
+/// `first_index`. For every point `X` the point `j` in `range(X)` with the
+/// largest `values[j]` plus distance to `X` is selected.
+fn solve_layer<F, R>(
+    tree: &BoxTree<F>,
+    flat_points: &[FlatPoint<F>],
+    values: &[F],
+    first_index: usize,
+    range: R,
+) -> Vec<GraphCell<F>>
+    where F: Float, R: Fn(usize) -> Range<usize> + Sync
+{
+    let maxima = tree.node_maxima(values);
+
+    let chunks: Vec<_> = (first_index..flat_points.len()).step_by(LAYER_CHUNK_SIZE).collect();
+    let layer: Vec<Vec<_>> = opt_par_iter(&chunks)
+        .map(|&chunk_start| {
+            let chunk_end = (chunk_start + LAYER_CHUNK_SIZE).min(flat_points.len());
+
+            // neighboring points usually select the same or a close point
+            let mut hint = None;
+            (chunk_start..chunk_end)
+                .map(|tp_index| {
+                    let tp = &flat_points[tp_index];
+                    match tree.farthest(flat_points, values, &maxima, tp, range(tp_index), hint) {
+                        Some((distance, prev_index)) => {
+                            hint = Some(prev_index);
+                            GraphCell { prev_index, distance }
+                        }
+                        None => GraphCell { prev_index: tp_index, distance: F::neg_infinity() },
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    layer.into_iter().flatten().collect()
+}
+
+struct GraphIterator<'a, F> {
+    graph: &'a Graph<F>,
     next: Option<(usize, usize)>,
     offset: usize,
 }
 
-impl Iterator for GraphIterator<'_> {
+impl<F> Iterator for GraphIterator<'_, F> {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.next.is_none() { return None; }
-
-        let (layer, index) = self.next.unwrap();
+        let (layer, index) = self.next?;
         self.next = if layer == 0 {
             None
         } else {
@@ -281,12 +872,19 @@ impl Iterator for GraphIterator<'_> {
     }
 }
 
-/// Calculates the total task distance (via haversine algorithm) from
-/// the original `route` and the arry of indices
+/// Calculates the altitude difference in meters between the first and the
+/// last point of the `path`
+fn height_loss(altitudes: &[i16], path: &Path) -> i32 {
+    let start = altitudes[*path.first().unwrap()];
+    let finish = altitudes[*path.last().unwrap()];
+    i32::from(start) - i32::from(finish)
+}
+
+/// Calculates the total task distance (via the given `formula`) in double
+/// precision from the original `route` and the arry of indices
 ///
-fn calculate_distance<T: Point>(points: &[T], path: &Path) -> f32 {
+fn calculate_distance<T: Point>(points: &[T], path: &Path, formula: DistanceFormula) -> f64 {
     path.iter().zip(path.iter().skip(1))
-        .map(|(i1, i2)| (&points[*i1], &points[*i2]))
-        .map(|(fix1, fix2)| haversine_distance(fix1, fix2))
+        .map(|(i1, i2)| formula.distance(&points[*i1], &points[*i2]))
         .sum()
 }
@@ -0,0 +1,95 @@
+use std::fmt;
+
+use failure::Fail;
+
+use crate::Point;
+use crate::time::increasing_indices;
+
+/// Reasons why no path can be found for a track
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptimizeError {
+    /// The path has to have at least one leg
+    InvalidLegs,
+    /// At least one path has to be requested
+    InvalidCount,
+    /// The track does not contain any fixes
+    EmptyTrack,
+    /// The track contains fewer (eligible) fixes than the path needs points
+    TooFewFixes { fixes: usize, required: usize },
+    /// The fix at `index` has a latitude or longitude that is NaN or infinite
+    NonFiniteCoordinates { index: usize },
+    /// Every path violates the altitude rule
+    NoValidSolution,
+    /// No part of the track without engine run contains enough fixes for a
+    /// path
+    NoPowerlessSegment,
+    /// GNSS altitudes were requested, but not every fix has one
+    MissingGnssAltitude,
+    /// Fix times are required, e.g. to detect engine runs, but not every fix
+    /// has one
+    MissingTimes,
+    /// Engine rules are not supported by the `LiveOptimizer`
+    UnsupportedEngineRule,
+    /// Ineligible fixes are not supported by the `LiveOptimizer`
+    UnsupportedIneligibleFixes,
+}
+
+impl fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            OptimizeError::InvalidLegs => write!(f, "at least one leg is required"),
+            OptimizeError::InvalidCount => write!(f, "at least one solution has to be requested"),
+            OptimizeError::EmptyTrack => write!(f, "the track does not contain any fixes"),
+            OptimizeError::TooFewFixes { fixes, required } => {
+                write!(f, "the track contains {} fixes, but at least {} are required", fixes, required)
+            }
+            OptimizeError::NonFiniteCoordinates { index } => {
+                write!(f, "the fix at index {} has non-finite coordinates", index)
+            }
+            OptimizeError::NoValidSolution => write!(f, "no path is valid under the altitude rule"),
+            OptimizeError::NoPowerlessSegment => {
+                write!(f, "no part of the track without engine run contains enough fixes")
+            }
+            OptimizeError::MissingGnssAltitude => write!(f, "GNSS altitudes are not available for every fix"),
+            OptimizeError::MissingTimes => write!(f, "times are not available for every fix"),
+            OptimizeError::UnsupportedEngineRule => write!(f, "engine rules are not supported by the live optimizer"),
+            OptimizeError::UnsupportedIneligibleFixes => {
+                write!(f, "ineligible fixes are not supported by the live optimizer")
+            }
+        }
+    }
+}
+
+impl Fail for OptimizeError {}
+
+/// Checks that a path with the given number of `legs` can be searched on the
+/// `route` and returns the indices of the fixes that are searched, see
+/// `check_track()`
+pub fn check_route<T: Point>(route: &[T], legs: usize) -> Result<Vec<usize>, OptimizeError> {
+    if legs == 0 {
+        return Err(OptimizeError::InvalidLegs);
+    }
+
+    check_track(route, legs + 1)
+}
+
+/// Checks that the `route` contains at least `min_fixes` fixes with finite
+/// coordinates and increasing times. Returns the indices of these fixes,
+/// which skip every fix that is not later than the fixes before it, e.g.
+/// because it was logged twice.
+pub fn check_track<T: Point>(route: &[T], min_fixes: usize) -> Result<Vec<usize>, OptimizeError> {
+    if route.is_empty() {
+        return Err(OptimizeError::EmptyTrack);
+    }
+
+    if let Some(index) = route.iter().position(|fix| !fix.latitude_f64().is_finite() || !fix.longitude_f64().is_finite()) {
+        return Err(OptimizeError::NonFiniteCoordinates { index });
+    }
+
+    let indices = increasing_indices(route);
+    if indices.len() < min_fixes {
+        return Err(OptimizeError::TooFewFixes { fixes: indices.len(), required: min_fixes });
+    }
+
+    Ok(indices)
+}
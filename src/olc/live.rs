@@ -0,0 +1,278 @@
+use std::cmp::Reverse;
+
+use failure::Error;
+use flat_projection::FlatPoint;
+use log::debug;
+use ord_subset::OrdVar;
+
+use crate::Point;
+use crate::altitude::AltitudeSource;
+use crate::flat::{project_around, project_points};
+use crate::float::Float;
+use crate::time::{is_later, timeline};
+use crate::tree::BoxTree;
+
+use super::error::{check_route, OptimizeError};
+use super::progress::Monitor;
+use super::{find_solution, flat_penalty, height_loss, route_altitudes, solve_graph, solve_layer, to_result, Graph, Problem, Solution};
+use super::{EngineRule, OptimizationResult, OptimizeOptions, Path, Precision};
+
+/// Optimizer for flights that are still in progress (e.g. live tracking).
+///
+/// Fixes are added one by one via `push()` and `current_best()` returns the
+/// best valid path for all accepted fixes. The flat projection is centered
+/// on the first fix and the solution graph of the previous calls is only
+/// extended by the new fixes, so that only paths that finish on one of the
+/// new fixes have to be searched.
+///
+/// `optimize_with()` centers the projection on the bounding box of the track
+/// instead, so `current_best()` can pick a different path if another valid
+/// path is shorter by less than the difference between the two projections.
+/// `finish()` returns the same result as `optimize_with()` for the complete
+/// flight.
+pub struct LiveOptimizer<T: Point> {
+    options: OptimizeOptions,
+    fixes: Vec<T>,
+    /// Time of the last accepted fix that has a time
+    last_time: Option<u32>,
+    state: State,
+}
+
+/// Search state with the precision of the options
+enum State {
+    Single(Search<f32>),
+    Double(Search<f64>),
+}
+
+/// Fixes that were searched by the previous `current_best()` calls
+struct Search<F> {
+    /// `(longitude, latitude)` of the first fix, which is the center of the
+    /// flat projection
+    center: Option<(f64, f64)>,
+    altitude_source: Option<AltitudeSource>,
+    flat_points: Vec<FlatPoint<F>>,
+    /// Solution graph without altitude rule, in which cell `j` of layer `l`
+    /// contains the best path with `l + 1` legs that finishes at point `j`
+    graph: Graph<F>,
+    /// Best valid path that finishes at one of the `flat_points`
+    best: Option<Solution<F>>,
+}
+
+impl<T: Point> LiveOptimizer<T> {
+    /// Creates an optimizer for the `options`. Engine rules and ineligible
+    /// fixes are not supported, because they depend on the complete track.
+    pub fn new(options: OptimizeOptions) -> Result<Self, OptimizeError> {
+        if options.legs == 0 {
+            return Err(OptimizeError::InvalidLegs);
+        }
+        if options.engine_rule != EngineRule::Ignore {
+            return Err(OptimizeError::UnsupportedEngineRule);
+        }
+        if !options.ineligible_fixes.is_empty() {
+            return Err(OptimizeError::UnsupportedIneligibleFixes);
+        }
+
+        let state = match options.precision {
+            Precision::Single => State::Single(Search::new(options.legs)),
+            Precision::Double => State::Double(Search::new(options.legs)),
+        };
+
+        Ok(LiveOptimizer { options, fixes: Vec::new(), last_time: None, state })
+    }
+
+    /// Adds the `fix` to the track. Returns `false` and ignores the fix if its
+    /// coordinates are not finite or if it is not later than the last
+    /// accepted fix, e.g. because it was received twice or out of order.
+    pub fn push(&mut self, fix: T) -> bool {
+        if !fix.latitude_f64().is_finite() || !fix.longitude_f64().is_finite() {
+            debug!("Ignoring fix with non-finite coordinates");
+            return false;
+        }
+
+        if let Some(time) = fix.time() {
+            if let Some(last_time) = self.last_time.filter(|&last_time| !is_later(time, last_time)) {
+                debug!("Ignoring fix at {} that is not later than the fix at {}", time, last_time);
+                return false;
+            }
+            self.last_time = Some(time);
+        }
+
+        self.fixes.push(fix);
+        true
+    }
+
+    /// All fixes that were accepted so far
+    pub fn fixes(&self) -> &[T] {
+        &self.fixes
+    }
+
+    /// Returns the best valid path for all accepted fixes, or `None` if not
+    /// enough fixes have been pushed yet
+    pub fn current_best(&mut self) -> Result<Option<OptimizationResult>, Error> {
+        match check_route(&self.fixes, self.options.legs) {
+            Err(OptimizeError::EmptyTrack) | Err(OptimizeError::TooFewFixes { .. }) => return Ok(None),
+            result => {
+                result?;
+            }
+        }
+
+        let (altitude_source, altitudes) = route_altitudes(&self.fixes, &self.options)?;
+        let path = match &mut self.state {
+            State::Single(search) => search.update(&self.fixes, &altitudes, altitude_source, &self.options)?,
+            State::Double(search) => search.update(&self.fixes, &altitudes, altitude_source, &self.options)?,
+        };
+
+        let times = timeline(&self.fixes);
+        Ok(Some(to_result(&self.fixes, &altitudes, times.as_deref(), &self.options, altitude_source, path)))
+    }
+
+    /// Returns the best valid path for the complete flight, which is the same
+    /// as the result of `optimize_with()` for all accepted fixes. The search
+    /// is repeated on the projection of `optimize_with()`, but starts from
+    /// the best path of the live search.
+    pub fn finish(mut self) -> Result<OptimizationResult, Error> {
+        check_route(&self.fixes, self.options.legs)?;
+
+        let (altitude_source, altitudes) = route_altitudes(&self.fixes, &self.options)?;
+        let (fixes, options) = (&self.fixes, &self.options);
+        let path = match &mut self.state {
+            State::Single(search) => search.finish(fixes, &altitudes, altitude_source, options)?,
+            State::Double(search) => search.finish(fixes, &altitudes, altitude_source, options)?,
+        };
+
+        let times = timeline(&self.fixes);
+        Ok(to_result(&self.fixes, &altitudes, times.as_deref(), &self.options, altitude_source, path))
+    }
+}
+
+impl<F: Float> Search<F> {
+    fn new(legs: usize) -> Self {
+        Search {
+            center: None,
+            altitude_source: None,
+            flat_points: Vec::new(),
+            graph: Graph { g: (0..legs).map(|_| Vec::new()).collect(), offset: 0, reversed: true },
+            best: None,
+        }
+    }
+
+    /// Adds the new `fixes` to the search and returns the best valid path
+    fn update<T: Point>(
+        &mut self,
+        fixes: &[T],
+        altitudes: &[i16],
+        altitude_source: AltitudeSource,
+        options: &OptimizeOptions,
+    ) -> Result<Path, OptimizeError> {
+        // the values of all paths change with the altitudes
+        if self.altitude_source != Some(altitude_source) {
+            debug!("Restarting the search with {:?} altitudes", altitude_source);
+            *self = Search::new(options.legs);
+            self.altitude_source = Some(altitude_source);
+        }
+
+        let center = *self.center.get_or_insert_with(|| (fixes[0].longitude_f64(), fixes[0].latitude_f64()));
+        let first_new = self.flat_points.len();
+        debug!("Converting {} new points to flat points", fixes.len() - first_new);
+        self.flat_points.extend(project_around::<F, T>(&fixes[first_new..], center));
+
+        debug!("Building bounding box tree");
+        let tree = BoxTree::new(&self.flat_points);
+
+        debug!("Extending solution graph");
+        let mut values = vec![F::zero(); self.flat_points.len()];
+        for layer in &mut self.graph.g {
+            layer.extend(solve_layer(&tree, &self.flat_points, &values, first_new, |index| 0..index));
+            values = layer.iter().map(|cell| cell.distance).collect();
+        }
+
+        if first_new == 0 {
+            let problem = Problem { flat_points: &self.flat_points, altitudes, options, first_finish: 0 };
+            self.best = find_solution(&problem, &mut Monitor::none());
+        } else {
+            self.search_finishes(altitudes, options, first_new);
+        }
+
+        self.best.as_ref().map(|best| best.path.clone()).ok_or(OptimizeError::NoValidSolution)
+    }
+
+    /// Adds the remaining `fixes` to the search and returns the best valid
+    /// path on the projection of `optimize_with()`
+    fn finish<T: Point>(
+        &mut self,
+        fixes: &[T],
+        altitudes: &[i16],
+        altitude_source: AltitudeSource,
+        options: &OptimizeOptions,
+    ) -> Result<Path, OptimizeError> {
+        let best = self.update(fixes, altitudes, altitude_source, options)?;
+        let flat_points = project_points::<F, T>(fixes).ok_or(OptimizeError::NoValidSolution)?;
+
+        // the best path of the live search is valid on every projection and
+        // is a lower bound for the search
+        let penalty = flat_penalty::<F>(&options.altitude_rule, height_loss(altitudes, &best))
+            .ok_or(OptimizeError::NoValidSolution)?;
+        let distance: F = best.windows(2)
+            .map(|pair| flat_points[pair[0]].distance(&flat_points[pair[1]]))
+            .sum();
+        let known = Solution { path: best, distance: distance - penalty };
+
+        debug!("Finishing the search on the projection of the whole track");
+        let problem = Problem { flat_points: &flat_points, altitudes, options, first_finish: 0 };
+        solve_graph(&problem, Some(known), &mut Monitor::none())
+            .map(|solution| solution.path)
+            .ok_or(OptimizeError::NoValidSolution)
+    }
+
+    /// Searches for better valid paths that finish at or after `first_new`
+    fn search_finishes(&mut self, altitudes: &[i16], options: &OptimizeOptions, first_new: usize) {
+        let last_layer = &self.graph.g[options.legs - 1];
+        let mut finishes: Vec<_> = (first_new..self.flat_points.len())
+            .filter(|&index| last_layer[index].distance > F::neg_infinity())
+            .collect();
+        finishes.sort_by_key(|&index| Reverse(OrdVar::new_checked(last_layer[index].distance)));
+
+        // upper bound for the valid paths to the finishes whose best path
+        // without altitude rule is not valid or has a penalty
+        let mut open_bound = None;
+        for finish in finishes {
+            // the best path to the finish without altitude rule is an upper
+            // bound for the valid paths
+            let upper_bound = self.graph.g[options.legs - 1][finish].distance;
+            if self.best.as_ref().is_some_and(|best| upper_bound <= best.distance) {
+                break;
+            }
+
+            let path = self.graph.path(finish);
+            match flat_penalty::<F>(&options.altitude_rule, height_loss(altitudes, &path)) {
+                Some(penalty) if penalty == F::zero() => {
+                    self.insert(Solution { path, distance: upper_bound });
+                    continue;
+                }
+                Some(penalty) => self.insert(Solution { path, distance: upper_bound - penalty }),
+                None => {}
+            }
+
+            open_bound.get_or_insert(upper_bound);
+        }
+
+        if open_bound.is_none_or(|bound| self.best.as_ref().is_some_and(|best| bound <= best.distance)) {
+            return;
+        }
+
+        // the start candidates of the search are bounded by the best path so far
+        debug!("Searching for better valid solutions that finish at or after index {}", first_new);
+        let problem = Problem { flat_points: &self.flat_points, altitudes, options, first_finish: first_new };
+        if let Some(solution) = solve_graph(&problem, self.best.clone(), &mut Monitor::none()) {
+            self.insert(solution);
+        }
+    }
+
+    /// Replaces the best path if the `solution` is better
+    fn insert(&mut self, solution: Solution<F>) {
+        if self.best.as_ref().is_none_or(|best| solution.distance > best.distance) {
+            debug!("-- New best solution: {:.3} km -> {:?}", solution.distance, solution.path);
+            self.best = Some(solution);
+        }
+    }
+}
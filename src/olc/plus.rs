@@ -0,0 +1,79 @@
+use failure::Error;
+use log::debug;
+
+use crate::Point;
+use crate::triangle::{self, TriangleOptions, TriangleResult};
+
+use super::{optimize_with, OptimizationResult, OptimizeOptions};
+
+/// Point factor of the free distance in the OLC-Plus score
+const CLASSIC_FACTOR: f64 = 1.0;
+
+/// Point factor of the FAI triangle distance in the OLC-Plus score
+const TRIANGLE_FACTOR: f64 = 0.3;
+
+#[derive(Debug, Clone)]
+pub struct PlusOptions {
+    /// Points per kilometer of the free distance
+    pub classic_factor: f64,
+    /// Points per kilometer of the FAI triangle distance
+    pub triangle_factor: f64,
+    /// Options of the free distance optimization
+    pub classic: OptimizeOptions,
+    /// Options of the FAI triangle optimization
+    pub triangle: TriangleOptions,
+}
+
+impl Default for PlusOptions {
+    fn default() -> Self {
+        PlusOptions {
+            classic_factor: CLASSIC_FACTOR,
+            triangle_factor: TRIANGLE_FACTOR,
+            classic: OptimizeOptions::default(),
+            triangle: TriangleOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PlusResult {
+    /// Free distance result
+    pub classic: OptimizationResult,
+    pub classic_factor: f64,
+    pub classic_points: f64,
+    /// FAI triangle result, or `None` if the flight contains no closed FAI triangle
+    pub triangle: Option<TriangleResult>,
+    pub triangle_factor: f64,
+    pub triangle_points: f64,
+    /// Sum of `classic_points` and `triangle_points`
+    pub score: f64,
+}
+
+/// Calculates the OLC-Plus score, which is the sum of the free distance
+/// points and the FAI triangle points of the flight.
+pub fn plus<T: Point>(route: &[T]) -> Result<PlusResult, Error> {
+    plus_with(route, &PlusOptions::default())
+}
+
+pub fn plus_with<T: Point>(route: &[T], options: &PlusOptions) -> Result<PlusResult, Error> {
+    debug!("Optimizing free distance");
+    let classic = optimize_with(route, &options.classic)?;
+    let classic_points = classic.distance * options.classic_factor;
+
+    debug!("Optimizing FAI triangle");
+    let triangle = triangle::optimize_with(route, &options.triangle)?;
+    let triangle_points = triangle.as_ref().map_or(0., |it| it.distance * options.triangle_factor);
+
+    let score = classic_points + triangle_points;
+    debug!("OLC-Plus score: {:.2} ({:.2} + {:.2})", score, classic_points, triangle_points);
+
+    Ok(PlusResult {
+        classic,
+        classic_factor: options.classic_factor,
+        classic_points,
+        triangle,
+        triangle_factor: options.triangle_factor,
+        triangle_points,
+        score,
+    })
+}
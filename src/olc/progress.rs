@@ -0,0 +1,109 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use failure::Fail;
+
+use super::OptimizationResult;
+
+/// Progress of a running `optimize_with_progress()` call
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Progress {
+    /// Leg number `leg` of `legs` of a solution graph for all start points
+    /// has been calculated. These graphs give the upper bounds for the start
+    /// candidates.
+    SolutionGraph {
+        leg: usize,
+        legs: usize,
+    },
+    /// The upper bounds of `candidates` start candidates are tightened with
+    /// the altitude rule and start altitude number `level` of `levels` is
+    /// analyzed, which calculates another solution graph
+    UpperBounds {
+        level: usize,
+        levels: usize,
+        candidates: usize,
+    },
+    /// A separate solution graph is calculated for start candidate number
+    /// `candidate` of currently `candidates` start candidates. Long tracks
+    /// go through the start candidates of the downsampled track first.
+    StartCandidate {
+        candidate: usize,
+        candidates: usize,
+        /// Distance of the best valid path found so far in kilometers on the
+        /// flat projection
+        best_distance: f64,
+    },
+}
+
+/// Token that can be used to cancel a running `optimize_with_progress()`
+/// call from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Error that is returned if the optimization was cancelled via the
+/// `CancellationToken`
+#[derive(Debug)]
+pub struct Cancelled {
+    /// Best valid path that was found before the optimization was cancelled
+    pub best: Option<OptimizationResult>,
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "optimization was cancelled")
+    }
+}
+
+impl Fail for Cancelled {}
+
+/// Forwards the progress of an optimization to the callback and keeps track
+/// of the cancellation
+pub struct Monitor<'a> {
+    callback: Option<&'a mut dyn FnMut(&Progress)>,
+    token: Option<&'a CancellationToken>,
+    cancelled: bool,
+}
+
+impl<'a> Monitor<'a> {
+    pub fn new(token: &'a CancellationToken, callback: &'a mut dyn FnMut(&Progress)) -> Self {
+        Monitor { callback: Some(callback), token: Some(token), cancelled: false }
+    }
+
+    /// Monitor that neither reports progress nor can be cancelled
+    pub fn none() -> Self {
+        Monitor { callback: None, token: None, cancelled: false }
+    }
+
+    pub fn report(&mut self, progress: Progress) {
+        if let Some(callback) = &mut self.callback {
+            callback(&progress);
+        }
+    }
+
+    /// Checks the `CancellationToken`. Once this returned `true` it will
+    /// always return `true`.
+    pub fn is_cancelled(&mut self) -> bool {
+        if !self.cancelled && self.token.is_some_and(|token| token.is_cancelled()) {
+            self.cancelled = true;
+        }
+        self.cancelled
+    }
+}
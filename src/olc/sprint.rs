@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use failure::Error;
+use flat_projection::FlatPoint;
+use log::debug;
+use ord_subset::OrdVar;
+
+use crate::Point;
+use crate::altitude::{altitudes, AltitudeSource};
+use crate::flat::project_points;
+use crate::float::Float;
+use crate::time::timeline;
+use crate::tree::{BoxTree, Node, TupleSearch};
+
+use super::error::check_route;
+use super::{calculate_distance, flat_penalty, height_loss, AltitudeRule, DistanceFormula, OptimizeError, Path, Precision};
+
+/// Length of the OLC League time window in seconds (2.5 hours)
+const WINDOW: u32 = 9000;
+
+/// Number of legs of the OLC League (start, three turnpoints, finish)
+const LEGS: usize = 4;
+
+/// Maximum height loss between start and finish in meters (OLC League rules)
+const MAX_HEIGHT_LOSS: i16 = 0;
+
+#[derive(Debug, Clone)]
+pub struct SprintOptions {
+    /// Length of the time window in seconds
+    pub window: u32,
+    /// Number of legs between start and finish
+    pub legs: usize,
+    pub altitude_rule: AltitudeRule,
+    /// Altitudes that are used for the `altitude_rule`
+    pub altitude_source: AltitudeSource,
+    /// Formula for the `distance` of the result
+    pub distance_formula: DistanceFormula,
+    pub precision: Precision,
+}
+
+impl Default for SprintOptions {
+    fn default() -> Self {
+        SprintOptions {
+            window: WINDOW,
+            legs: LEGS,
+            altitude_rule: AltitudeRule::MaxHeightLoss(MAX_HEIGHT_LOSS),
+            altitude_source: AltitudeSource::Auto,
+            distance_formula: DistanceFormula::Haversine,
+            precision: Precision::Single,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SprintResult {
+    pub path: Path,
+    /// Scored distance in kilometers, i.e. the sum of all legs minus the
+    /// `penalty`, calculated in double precision
+    pub distance: f64,
+    /// Average speed of the scored distance over the time window in km/h
+    pub speed: f64,
+    /// Time of the start fix in seconds since midnight
+    pub start_time: u32,
+    /// Time of the finish fix in seconds since midnight of the start day
+    pub finish_time: u32,
+    /// Altitude rule that was applied to the path
+    pub altitude_rule: AltitudeRule,
+    /// Altitudes that were used for the `altitude_rule`
+    pub altitude_source: AltitudeSource,
+    /// Start altitude minus finish altitude in meters
+    pub height_loss: i32,
+    /// Distance penalty in kilometers caused by the `altitude_rule`
+    pub penalty: f64,
+}
+
+impl SprintResult {
+    /// Time between start and finish in seconds
+    pub fn duration(&self) -> Option<u32> {
+        self.finish_time.checked_sub(self.start_time)
+    }
+}
+
+/// Finds the largest distance that was flown within the OLC League time
+/// window, or `None` if the flight contains no valid path.
+///
+/// All fixes need to have a `Point::time()`.
+pub fn sprint<T: Point>(route: &[T]) -> Result<Option<SprintResult>, Error> {
+    sprint_with(route, &SprintOptions::default())
+}
+
+pub fn sprint_with<T: Point>(route: &[T], options: &SprintOptions) -> Result<Option<SprintResult>, Error> {
+    let indices = check_route(route, options.legs)?;
+    let fixes: Vec<&T> = indices.iter().map(|&index| &route[index]).collect();
+
+    let times = timeline(&fixes).ok_or(OptimizeError::MissingTimes)?;
+    let (altitude_source, altitudes) = altitudes(&fixes, options.altitude_source)
+        .ok_or(OptimizeError::MissingGnssAltitude)?;
+
+    let path = match options.precision {
+        Precision::Single => search::<f32, &T>(&fixes, &times, &altitudes, options),
+        Precision::Double => search::<f64, &T>(&fixes, &times, &altitudes, options),
+    };
+
+    let path = match path {
+        Some(path) => path,
+        None => {
+            debug!("No valid path found");
+            return Ok(None);
+        }
+    };
+
+    let height_loss = height_loss(&altitudes, &path);
+    let penalty = options.altitude_rule.penalty(height_loss).unwrap_or(0.);
+    let distance = calculate_distance(&fixes, &path, options.distance_formula) - penalty;
+    let speed = distance / (f64::from(options.window) / 3600.);
+    let start_time = times[path[0]];
+    let finish_time = times[path[path.len() - 1]];
+    let path: Path = path.into_iter().map(|index| indices[index]).collect();
+    debug!("Solution: {:?} ({:.3} km, {:.2} km/h)", path, distance, speed);
+
+    Ok(Some(SprintResult {
+        path,
+        distance,
+        speed,
+        start_time,
+        finish_time,
+        altitude_rule: options.altitude_rule,
+        altitude_source,
+        height_loss,
+        penalty,
+    }))
+}
+
+/// Finds the largest distance within the time window on the flat surface
+/// with the precision of `F`
+fn search<F: Float, T: Point>(route: &[T], times: &[u32], altitudes: &[i16], options: &SprintOptions) -> Option<Path> {
+    debug!("Converting {} points to flat points", route.len());
+    let flat_points = project_points::<F, T>(route)?;
+
+    debug!("Building bounding box tree");
+    let tree = BoxTree::new(&flat_points);
+
+    debug!("Searching for best path within {} seconds", options.window);
+    let mut search = Search {
+        points: &flat_points,
+        times,
+        altitudes,
+        altitude_ranges: HashMap::new(),
+        options,
+    };
+
+    tree.search_tuples(&mut search)
+}
+
+/// Search for the largest distance within the time window via `BoxTree::search_tuples()`
+struct Search<'a, F> {
+    points: &'a [FlatPoint<F>],
+    times: &'a [u32],
+    altitudes: &'a [i16],
+    altitude_ranges: HashMap<(usize, usize), (i16, i16)>,
+    options: &'a SprintOptions,
+}
+
+impl<F: Float> TupleSearch<F> for Search<'_, F> {
+    type Solution = Path;
+
+    fn size(&self) -> usize {
+        self.options.legs + 1
+    }
+
+    fn upper_bound(&mut self, nodes: &[&Node<F>], best_distance: F) -> Option<F> {
+        let (start, finish) = (nodes[0], nodes[nodes.len() - 1]);
+
+        let min_duration = self.times[finish.start].saturating_sub(self.times[start.end - 1]);
+        if min_duration > self.options.window {
+            return None;
+        }
+
+        // the penalty grows with the height loss, so the smallest height loss
+        // between the nodes gives the smallest penalty
+        let (min_start_altitude, _) = self.altitude_range(start);
+        let (_, max_finish_altitude) = self.altitude_range(finish);
+        let min_height_loss = i32::from(min_start_altitude) - i32::from(max_finish_altitude);
+        let min_penalty: F = flat_penalty(&self.options.altitude_rule, min_height_loss)?;
+
+        let max_distance: F = nodes.windows(2)
+            .map(|pair| pair[0].bbox.max_distance(&pair[1].bbox))
+            .sum::<F>() - min_penalty;
+
+        if max_distance <= best_distance {
+            return None;
+        }
+
+        Some(max_distance)
+    }
+
+    fn search_leaves(&mut self, nodes: &[&Node<F>], best_distance: F) -> Option<(F, Path)> {
+        let mut best = None;
+        let mut best_distance = best_distance;
+
+        for start in nodes[0].start..nodes[0].end {
+            let layers = leaf_layers(self.points, start, nodes);
+
+            let max_time = self.times[start].saturating_add(self.options.window);
+
+            let finish = layers.last().unwrap().iter()
+                .enumerate()
+                .filter(|(_, &(index, _, _))| self.times[index] <= max_time)
+                .filter_map(|(i, &(index, distance, _))| {
+                    let height_loss = i32::from(self.altitudes[start]) - i32::from(self.altitudes[index]);
+                    let penalty: F = flat_penalty(&self.options.altitude_rule, height_loss)?;
+                    Some((i, distance - penalty))
+                })
+                .filter(|&(_, distance)| distance > best_distance)
+                .max_by_key(|&(_, distance)| OrdVar::new(distance));
+
+            if let Some((i, distance)) = finish {
+                best_distance = distance;
+                best = Some((distance, leaf_path(&layers, i)));
+            }
+        }
+
+        best
+    }
+}
+
+impl<F> Search<'_, F> {
+    /// Returns the minimum and maximum altitude of the fixes in `node`
+    fn altitude_range(&mut self, node: &Node<F>) -> (i16, i16) {
+        let altitudes = &self.altitudes[node.start..node.end];
+        *self.altitude_ranges.entry((node.start, node.end))
+            .or_insert_with(|| (*altitudes.iter().min().unwrap(), *altitudes.iter().max().unwrap()))
+    }
+}
+
+/// Calculates the longest paths from `start` through the given leaf nodes,
+/// with one layer per node. Every layer contains `(index, distance, index of
+/// the previous point in the last layer)`.
+fn leaf_layers<F: Float>(points: &[FlatPoint<F>], start: usize, nodes: &[&Node<F>]) -> Vec<Vec<(usize, F, usize)>> {
+    let mut layers: Vec<Vec<(usize, F, usize)>> = vec![vec![(start, F::zero(), 0)]];
+
+    for node in &nodes[1..] {
+        let last_layer = layers.last().unwrap();
+        let layer = (node.start..node.end)
+            .filter_map(|index| last_layer.iter()
+                .enumerate()
+                .filter(|(_, &(prev_index, _, _))| prev_index < index)
+                .map(|(i, &(prev_index, distance, _))| (i, distance + points[prev_index].distance(&points[index])))
+                .max_by_key(|&(_, distance)| OrdVar::new(distance))
+                .map(|(i, distance)| (index, distance, i)))
+            .collect();
+
+        layers.push(layer);
+    }
+
+    layers
+}
+
+/// Follows the `leaf_layers()` backwards from the `i`-th point of the last layer
+fn leaf_path<F>(layers: &[Vec<(usize, F, usize)>], mut i: usize) -> Path {
+    let mut path = Vec::with_capacity(layers.len());
+    for layer in layers.iter().rev() {
+        let (index, _, prev) = layer[i];
+        path.push(index);
+        i = prev;
+    }
+    path.reverse();
+    path
+}
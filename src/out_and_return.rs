@@ -0,0 +1,150 @@
+use failure::Error;
+use flat_projection::FlatPoint;
+use log::debug;
+
+use crate::Point;
+use crate::flat::project_points;
+use crate::float::Float;
+use crate::olc::{check_track, DistanceFormula, Precision};
+use crate::tree::{BoxTree, Node, TupleSearch};
+
+/// Maximum distance between start and finish relative to the out-and-return distance
+const MAX_CLOSING_RATIO: f64 = 0.2;
+
+/// Number of fixes that start, turnpoint and finish need at least
+const MIN_FIXES: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct OutAndReturnOptions {
+    /// Maximum distance between start and finish as a fraction of the
+    /// out-and-return distance
+    pub max_closing_ratio: f64,
+    /// Formula for the `distance` of the result
+    pub distance_formula: DistanceFormula,
+    pub precision: Precision,
+}
+
+impl Default for OutAndReturnOptions {
+    fn default() -> Self {
+        OutAndReturnOptions {
+            max_closing_ratio: MAX_CLOSING_RATIO,
+            distance_formula: DistanceFormula::Haversine,
+            precision: Precision::Single,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OutAndReturnResult {
+    pub start: usize,
+    pub turnpoint: usize,
+    pub finish: usize,
+    /// Sum of the outbound and the return leg in kilometers, calculated in
+    /// double precision
+    pub distance: f64,
+    /// Distance between `start` and `finish` in kilometers
+    pub closing_distance: f64,
+}
+
+/// Finds the farthest out-and-return in the `route`, or `None` if the flight
+/// does not contain a closed out-and-return.
+pub fn optimize<T: Point>(route: &[T]) -> Result<Option<OutAndReturnResult>, Error> {
+    optimize_with(route, &OutAndReturnOptions::default())
+}
+
+pub fn optimize_with<T: Point>(route: &[T], options: &OutAndReturnOptions) -> Result<Option<OutAndReturnResult>, Error> {
+    let indices = check_track(route, MIN_FIXES)?;
+    let fixes: Vec<&T> = indices.iter().map(|&index| &route[index]).collect();
+
+    let solution = match options.precision {
+        Precision::Single => search::<f32, &T>(&fixes, options),
+        Precision::Double => search::<f64, &T>(&fixes, options),
+    };
+
+    let [start, turnpoint, finish] = match solution {
+        Some(solution) => solution.map(|index| indices[index]),
+        None => {
+            debug!("No valid out-and-return found");
+            return Ok(None);
+        }
+    };
+
+    let formula = options.distance_formula;
+    let distance = formula.distance(&route[start], &route[turnpoint]) +
+        formula.distance(&route[turnpoint], &route[finish]);
+
+    let closing_distance = formula.distance(&route[start], &route[finish]);
+    debug!("Solution: {:?} ({:.3} km)", [start, turnpoint, finish], distance);
+
+    Ok(Some(OutAndReturnResult { start, turnpoint, finish, distance, closing_distance }))
+}
+
+/// Finds the farthest out-and-return on the flat surface with the precision
+/// of `F`
+fn search<F: Float, T: Point>(route: &[T], options: &OutAndReturnOptions) -> Option<[usize; 3]> {
+    debug!("Converting {} points to flat points", route.len());
+    let flat_points = project_points::<F, T>(route)?;
+
+    debug!("Building bounding box tree");
+    let tree = BoxTree::new(&flat_points);
+
+    debug!("Searching for best out-and-return");
+    let mut search = Search { points: &flat_points, max_closing_ratio: F::from_f64(options.max_closing_ratio) };
+    tree.search_tuples(&mut search)
+}
+
+/// Search for the farthest out-and-return via `BoxTree::search_tuples()`
+struct Search<'a, F> {
+    points: &'a [FlatPoint<F>],
+    max_closing_ratio: F,
+}
+
+impl<F: Float> TupleSearch<F> for Search<'_, F> {
+    type Solution = [usize; 3];
+
+    fn size(&self) -> usize {
+        3
+    }
+
+    fn upper_bound(&mut self, nodes: &[&Node<F>], best_distance: F) -> Option<F> {
+        let (start, turnpoint, finish) = (nodes[0], nodes[1], nodes[2]);
+        let max_distance = start.bbox.max_distance(&turnpoint.bbox) + turnpoint.bbox.max_distance(&finish.bbox);
+        if max_distance <= best_distance {
+            return None;
+        }
+
+        let min_closing = start.bbox.min_distance(&finish.bbox);
+        if min_closing > self.max_closing_ratio * max_distance {
+            return None;
+        }
+
+        Some(max_distance)
+    }
+
+    fn search_leaves(&mut self, nodes: &[&Node<F>], best_distance: F) -> Option<(F, [usize; 3])> {
+        let (start, turnpoint, finish) = (nodes[0], nodes[1], nodes[2]);
+        let mut best = None;
+        let mut best_distance = best_distance;
+
+        for s in start.start..start.end {
+            for t in (turnpoint.start..turnpoint.end).filter(|&t| t > s) {
+                let outbound = self.points[s].distance(&self.points[t]);
+
+                for f in (finish.start..finish.end).filter(|&f| f > t) {
+                    let distance = outbound + self.points[t].distance(&self.points[f]);
+                    if distance <= best_distance {
+                        continue;
+                    }
+
+                    let closing = self.points[s].distance(&self.points[f]);
+                    if closing <= self.max_closing_ratio * distance {
+                        best_distance = distance;
+                        best = Some((distance, [s, t, f]));
+                    }
+                }
+            }
+        }
+
+        best
+    }
+}
@@ -0,0 +1,158 @@
+//! Detection of physically impossible fixes, e.g. GPS jumps
+
+use flat_projection::FlatPoint;
+
+use crate::Point;
+use crate::flat::project_points;
+use crate::time::increasing_timeline;
+
+/// Maximum ground speed in km/h
+const MAX_SPEED: f64 = 500.;
+
+/// Maximum horizontal acceleration in m/s² (about 5 g)
+const MAX_ACCELERATION: f64 = 50.;
+
+/// Maximum vertical speed in m/s
+const MAX_VERTICAL_SPEED: f64 = 50.;
+
+#[derive(Debug, Clone)]
+pub struct OutlierOptions {
+    /// Maximum ground speed in km/h
+    pub max_speed: f64,
+    /// Maximum horizontal acceleration in m/s²
+    pub max_acceleration: f64,
+    /// Maximum climb or sink rate in m/s
+    pub max_vertical_speed: f64,
+}
+
+impl Default for OutlierOptions {
+    fn default() -> Self {
+        OutlierOptions {
+            max_speed: MAX_SPEED,
+            max_acceleration: MAX_ACCELERATION,
+            max_vertical_speed: MAX_VERTICAL_SPEED,
+        }
+    }
+}
+
+/// Threshold that a fix exceeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlierReason {
+    Speed,
+    Acceleration,
+    VerticalSpeed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outlier {
+    /// Index of the fix in the original track
+    pub index: usize,
+    pub reason: OutlierReason,
+}
+
+#[derive(Debug, Clone)]
+pub struct FilteredTrack<T> {
+    pub fixes: Vec<T>,
+    /// Fixes that were removed
+    pub outliers: Vec<Outlier>,
+}
+
+/// Finds the fixes that can not be reached from the previous plausible fix
+/// within the thresholds of the `options`. The first fix is considered to be
+/// plausible unless it can not reach the second fix, while the second fix can
+/// reach the third one.
+///
+/// The GNSS altitudes are checked if available, because GPS jumps usually do
+/// not affect the pressure altitude. Returns `None` if any of the fixes has
+/// no time or is not later than the fix before it (see `track::sanitize()`).
+pub fn find_outliers<T: Point>(route: &[T], options: &OutlierOptions) -> Option<Vec<Outlier>> {
+    if route.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let times = increasing_timeline(route)?;
+    let points = project_points::<f64, T>(route)?;
+    let fixes: Vec<_> = points.iter().zip(&times).zip(route)
+        .map(|((point, &time), fix)| Motion {
+            point,
+            time,
+            altitude: fix.gnss_altitude().unwrap_or_else(|| fix.altitude()),
+        })
+        .collect();
+
+    let mut outliers = Vec::new();
+    let mut first = 0;
+    if fixes.len() >= 3 {
+        let reason = check(&fixes[0], &fixes[1], None, options);
+        if let Some(reason) = reason.filter(|_| check(&fixes[1], &fixes[2], None, options).is_none()) {
+            outliers.push(Outlier { index: 0, reason });
+            first = 1;
+        }
+    }
+
+    // the two last plausible fixes
+    let mut previous: (Option<usize>, usize) = (None, first);
+    for i in first + 1..fixes.len() {
+        let before = previous.0.map(|j| &fixes[j]);
+        match check(&fixes[previous.1], &fixes[i], before, options) {
+            Some(reason) => outliers.push(Outlier { index: i, reason }),
+            None => previous = (Some(previous.1), i),
+        }
+    }
+
+    Some(outliers)
+}
+
+/// Removes the outliers of `find_outliers()` from the `fixes`. Tracks for
+/// which `find_outliers()` returns `None` are returned unchanged.
+pub fn remove_outliers<T: Point>(fixes: Vec<T>, options: &OutlierOptions) -> FilteredTrack<T> {
+    let outliers = find_outliers(&fixes, options).unwrap_or_default();
+
+    let mut remaining = outliers.iter().map(|it| it.index).peekable();
+    let fixes = fixes.into_iter()
+        .enumerate()
+        .filter(|&(index, _)| remaining.next_if_eq(&index).is_none())
+        .map(|(_, fix)| fix)
+        .collect();
+
+    FilteredTrack { fixes, outliers }
+}
+
+struct Motion<'a> {
+    point: &'a FlatPoint<f64>,
+    time: u32,
+    altitude: i16,
+}
+
+/// Returns the first threshold that is exceeded on the way from `from` to
+/// `to`, with `before` being the plausible fix before `from`
+fn check(from: &Motion<'_>, to: &Motion<'_>, before: Option<&Motion<'_>>, options: &OutlierOptions) -> Option<OutlierReason> {
+    let duration = f64::from(to.time - from.time);
+
+    // velocity in m/s
+    let velocity = |from: &Motion<'_>, to: &Motion<'_>, duration: f64| {
+        ((to.point.x - from.point.x) * 1000. / duration, (to.point.y - from.point.y) * 1000. / duration)
+    };
+
+    let (vx, vy) = velocity(from, to, duration);
+    if vx.hypot(vy) * 3.6 > options.max_speed {
+        return Some(OutlierReason::Speed);
+    }
+
+    let climb = f64::from(i32::from(to.altitude) - i32::from(from.altitude)) / duration;
+    if climb.abs() > options.max_vertical_speed {
+        return Some(OutlierReason::VerticalSpeed);
+    }
+
+    if let Some(before) = before {
+        let (previous_vx, previous_vy) = velocity(before, from, f64::from(from.time - before.time));
+
+        // the velocities belong to the middle of their intervals
+        let interval = f64::from(to.time - before.time) / 2.;
+        if (vx - previous_vx).hypot(vy - previous_vy) / interval > options.max_acceleration {
+            return Some(OutlierReason::Acceleration);
+        }
+    }
+
+    None
+}
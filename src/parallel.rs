@@ -5,14 +5,14 @@ cfg_if! {
         use rayon::slice;
         pub use rayon::prelude::*;
 
-        pub fn opt_par_iter<T: Sync>(x: &[T]) -> slice::Iter<T> {
+        pub fn opt_par_iter<T: Sync>(x: &[T]) -> slice::Iter<'_, T> {
             x.par_iter()
         }
 
     } else {
         use std::slice;
 
-        pub fn opt_par_iter<T>(x: &[T]) -> slice::Iter<T> {
+        pub fn opt_par_iter<T>(x: &[T]) -> slice::Iter<'_, T> {
             x.iter()
         }
     }
@@ -0,0 +1,132 @@
+//! Detection of takeoff, launch and landing in a timed track, so that the
+//! optimizers can be run on the free flight only
+
+use std::ops::Range;
+
+use flat_projection::FlatPoint;
+
+use crate::Point;
+use crate::altitude::{altitudes, AltitudeSource};
+use crate::flat::project_points;
+use crate::heading::turns;
+use crate::time::increasing_timeline;
+
+/// Ground speed in km/h above which the glider is considered to be flying
+const MIN_FLYING_SPEED: f64 = 40.;
+
+/// Time window in seconds over which the ground speed is measured
+const SPEED_WINDOW: u32 = 10;
+
+/// Altitude loss in meters below the highest point of the launch after which
+/// the launch is considered to be over
+const RELEASE_ALTITUDE_LOSS: i32 = 100;
+
+/// Turn in degrees within `TURN_WINDOW` that is too steep for an aerotow
+const RELEASE_TURN: f64 = 160.;
+
+/// Time window in seconds over which the turn is measured
+const TURN_WINDOW: u32 = 30;
+
+/// Launches that end within this many seconds after takeoff are winch launches
+const MAX_WINCH_LAUNCH_DURATION: u32 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchMethod {
+    Aerotow,
+    Winch,
+}
+
+/// Indices of the fixes where the phases of a flight begin or end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlightPhases {
+    /// First fix of the takeoff run
+    pub takeoff: usize,
+    /// Fix at the end of the launch, i.e. the highest point of the tow or
+    /// winch launch
+    pub release: usize,
+    /// First fix after the landing roll
+    pub landing: usize,
+    pub launch: LaunchMethod,
+}
+
+impl FlightPhases {
+    /// Returns the index range of the fixes from the release to the landing
+    pub fn free_flight(&self) -> Range<usize> {
+        self.release..self.landing + 1
+    }
+}
+
+/// Detects takeoff, release and landing of the flight.
+///
+/// Returns `None` if any of the fixes has no time or is not later than the
+/// fix before it, or if the glider never reaches flying speed.
+pub fn detect<T: Point>(route: &[T]) -> Option<FlightPhases> {
+    let times = increasing_timeline(route)?;
+    let (_, altitudes) = altitudes(route, AltitudeSource::Auto)?;
+    if route.len() < 2 {
+        return None;
+    }
+
+    let points = project_points::<f64, T>(route)?;
+    let flying = flying_windows(&points, &times);
+
+    let takeoff = flying.iter().position(|window| window.is_some())?;
+    let landing = flying.iter().rev().filter_map(|window| *window).next()?;
+    let release = find_release(&points, &times, &altitudes, takeoff, landing);
+
+    let launch = if times[release] - times[takeoff] <= MAX_WINCH_LAUNCH_DURATION {
+        LaunchMethod::Winch
+    } else {
+        LaunchMethod::Aerotow
+    };
+
+    Some(FlightPhases { takeoff, release, landing, launch })
+}
+
+/// Returns for every fix the end of its speed window, if the ground speed
+/// within the window is above `MIN_FLYING_SPEED`
+fn flying_windows(points: &[FlatPoint<f64>], times: &[u32]) -> Vec<Option<usize>> {
+    let mut end = 0;
+    (0..points.len())
+        .map(|start| {
+            end = end.max(start);
+            while end + 1 < points.len() && times[end] < times[start] + SPEED_WINDOW {
+                end += 1;
+            }
+
+            let duration = times[end] - times[start];
+            if duration == 0 {
+                return None;
+            }
+
+            let speed = points[start].distance(&points[end]) / f64::from(duration) * 3600.;
+            Some(end).filter(|_| speed > MIN_FLYING_SPEED)
+        })
+        .collect()
+}
+
+/// Returns the highest fix of the launch, which ends when the glider has lost
+/// `RELEASE_ALTITUDE_LOSS` or starts circling
+fn find_release(points: &[FlatPoint<f64>], times: &[u32], altitudes: &[i16], takeoff: usize, landing: usize) -> usize {
+    let turns = turns(points);
+    let mut release = takeoff;
+    let mut window_start = takeoff;
+
+    for i in takeoff..landing {
+        if altitudes[i] >= altitudes[release] {
+            release = i;
+        }
+        if i32::from(altitudes[i]) < i32::from(altitudes[release]) - RELEASE_ALTITUDE_LOSS {
+            break;
+        }
+
+        while times[window_start] + TURN_WINDOW <= times[i + 1] {
+            window_start += 1;
+        }
+        if turns[window_start..=i].iter().sum::<f64>().abs() >= RELEASE_TURN {
+            break;
+        }
+    }
+
+    release
+}
@@ -1,5 +1,81 @@
 pub trait Point: Sync {
     fn latitude(&self) -> f32;
     fn longitude(&self) -> f32;
+
+    /// Latitude in double precision. Implementations that store the
+    /// coordinates in double precision should override this and
+    /// `longitude_f64()`.
+    fn latitude_f64(&self) -> f64 {
+        f64::from(self.latitude())
+    }
+
+    /// Longitude in double precision
+    fn longitude_f64(&self) -> f64 {
+        f64::from(self.longitude())
+    }
+
+    /// Pressure altitude of the fix in meters
     fn altitude(&self) -> i16;
+
+    /// GNSS altitude of the fix in meters, if available
+    fn gnss_altitude(&self) -> Option<i16> {
+        None
+    }
+
+    /// Time of the fix in seconds since midnight (UTC), if available
+    ///
+    /// The time may wrap around at midnight, the optimizers use
+    /// `time::timeline()` to calculate continuous times for the whole flight.
+    fn time(&self) -> Option<u32> {
+        None
+    }
+
+    /// Environmental noise level (ENL) of the fix from 0 to 999, if available
+    fn enl(&self) -> Option<u16> {
+        None
+    }
+
+    /// Means of propulsion (MOP) sensor level of the fix from 0 to 999, if
+    /// available
+    fn mop(&self) -> Option<u16> {
+        None
+    }
+}
+
+impl<T: Point + ?Sized> Point for &T {
+    fn latitude(&self) -> f32 {
+        (**self).latitude()
+    }
+
+    fn longitude(&self) -> f32 {
+        (**self).longitude()
+    }
+
+    fn latitude_f64(&self) -> f64 {
+        (**self).latitude_f64()
+    }
+
+    fn longitude_f64(&self) -> f64 {
+        (**self).longitude_f64()
+    }
+
+    fn altitude(&self) -> i16 {
+        (**self).altitude()
+    }
+
+    fn gnss_altitude(&self) -> Option<i16> {
+        (**self).gnss_altitude()
+    }
+
+    fn time(&self) -> Option<u32> {
+        (**self).time()
+    }
+
+    fn enl(&self) -> Option<u16> {
+        (**self).enl()
+    }
+
+    fn mop(&self) -> Option<u16> {
+        (**self).mop()
+    }
 }
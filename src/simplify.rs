@@ -0,0 +1,79 @@
+//! Simplification of tracks, e.g. for map rendering or fast preview scoring.
+//! All functions return the indices of the selected fixes in ascending order.
+
+use flat_projection::FlatPoint;
+use ord_subset::OrdSubsetIterExt;
+
+use crate::Point;
+use crate::flat::project_points;
+use crate::time::timeline;
+
+/// Simplifies the `route` with the Douglas-Peucker algorithm on the flat
+/// projection, so that no fix is farther than `tolerance` kilometers from the
+/// simplified track. The first and the last fix are always selected.
+pub fn douglas_peucker<T: Point>(route: &[T], tolerance: f64) -> Vec<usize> {
+    if route.len() <= 2 {
+        return (0..route.len()).collect();
+    }
+
+    let points = match project_points::<f64, T>(route) {
+        Some(points) => points,
+        None => return (0..route.len()).collect(),
+    };
+    let mut selected = vec![false; points.len()];
+    selected[0] = true;
+    selected[points.len() - 1] = true;
+
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = ranges.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| (i, segment_distance(&points[i], &points[first], &points[last])))
+            .ord_subset_max_by_key(|&(_, distance)| distance);
+
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                selected[i] = true;
+                ranges.push((first, i));
+                ranges.push((i, last));
+            }
+        }
+    }
+
+    (0..points.len()).filter(|&i| selected[i]).collect()
+}
+
+/// Selects the first fix and then every fix that is at least `interval`
+/// seconds after the previously selected fix. The last fix is always
+/// selected.
+///
+/// Returns `None` if any of the fixes has no time.
+pub fn resample<T: Point>(route: &[T], interval: u32) -> Option<Vec<usize>> {
+    let times = timeline(route)?;
+
+    let mut indices: Vec<usize> = Vec::new();
+    for (i, &time) in times.iter().enumerate() {
+        if indices.last().is_none_or(|&last| time >= times[last].saturating_add(interval)) {
+            indices.push(i);
+        }
+    }
+
+    if !route.is_empty() && indices.last() != Some(&(route.len() - 1)) {
+        indices.push(route.len() - 1);
+    }
+
+    Some(indices)
+}
+
+/// Returns the distance in kilometers between `point` and the line segment
+/// from `start` to `end`
+fn segment_distance(point: &FlatPoint<f64>, start: &FlatPoint<f64>, end: &FlatPoint<f64>) -> f64 {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length_squared = dx * dx + dy * dy;
+    if length_squared == 0. {
+        return point.distance(start);
+    }
+
+    let t = (((point.x - start.x) * dx + (point.y - start.y) * dy) / length_squared).clamp(0., 1.);
+    let projection = FlatPoint { x: start.x + t * dx, y: start.y + t * dy };
+    point.distance(&projection)
+}
@@ -0,0 +1,179 @@
+//! Segmentation of a timed track into circling and cruising phases
+
+use flat_projection::FlatPoint;
+
+use crate::Point;
+use crate::altitude::{altitudes, AltitudeSource};
+use crate::flat::project_points;
+use crate::heading::turns;
+use crate::time::increasing_timeline;
+
+/// Time window in seconds over which the turn rate of a fix is measured
+const TURN_WINDOW: u32 = 20;
+
+/// Turn rate in degrees per second above which circling starts
+const MIN_ENTRY_TURN_RATE: f64 = 8.;
+
+/// Turn rate in degrees per second below which circling ends
+const MAX_EXIT_TURN_RATE: f64 = 4.;
+
+/// Minimum duration of a thermal in seconds, which filters S-turns and
+/// single turns onto a new course
+const MIN_THERMAL_DURATION: u32 = 45;
+
+/// Minimum duration of a cruise between two circling phases in seconds.
+/// Shorter breaks are usually made to center the thermal.
+const MIN_CRUISE_DURATION: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    Circling,
+    Cruising,
+}
+
+/// Consecutive fixes from `start` to `end` (inclusive) that are flown in the
+/// same way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thermal {
+    /// Index of the first circling fix
+    pub entry: usize,
+    /// Index of the last circling fix
+    pub exit: usize,
+    /// Altitude difference in meters between exit and entry
+    pub altitude_gain: i32,
+    /// Average climb rate in m/s
+    pub climb_rate: f64,
+    /// Direction in degrees clockwise from north into which the circles
+    /// drift with the wind, or `None` if they do not move
+    pub drift_direction: Option<f64>,
+    /// Drift speed of the circles in km/h
+    pub drift_speed: f64,
+}
+
+/// Splits the `route` into alternating circling and cruising segments. Fixes
+/// start circling when their turn rate exceeds `MIN_ENTRY_TURN_RATE` and stop
+/// when it drops below `MAX_EXIT_TURN_RATE`.
+///
+/// Returns `None` if any of the fixes has no time or is not later than the
+/// fix before it.
+pub fn segments<T: Point>(route: &[T]) -> Option<Vec<Segment>> {
+    if route.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let times = increasing_timeline(route)?;
+    let points = project_points::<f64, T>(route)?;
+    let rates = turn_rates(&points, &times);
+
+    let mut circling = vec![false; route.len()];
+    let mut state = false;
+    for (i, &rate) in rates.iter().enumerate() {
+        state = if state { rate >= MAX_EXIT_TURN_RATE } else { rate > MIN_ENTRY_TURN_RATE };
+        circling[i] = state;
+    }
+
+    // short cruises between circles belong to the thermal, while short
+    // circling phases are turns that belong to the cruise
+    let segments = to_segments(&circling);
+    for segment in segments.iter().filter(|it| it.kind == SegmentKind::Cruising && it.start > 0 && it.end + 1 < route.len()) {
+        if times[segment.end + 1] - times[segment.start - 1] < MIN_CRUISE_DURATION {
+            circling[segment.start..=segment.end].iter_mut().for_each(|circling| *circling = true);
+        }
+    }
+
+    let segments = to_segments(&circling);
+    for segment in segments.iter().filter(|it| it.kind == SegmentKind::Circling) {
+        if times[segment.end] - times[segment.start] < MIN_THERMAL_DURATION {
+            circling[segment.start..=segment.end].iter_mut().for_each(|circling| *circling = false);
+        }
+    }
+
+    Some(to_segments(&circling))
+}
+
+/// Returns all circling segments of `segments()` as thermals
+pub fn thermals<T: Point>(route: &[T]) -> Option<Vec<Thermal>> {
+    if route.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let times = increasing_timeline(route)?;
+    let (_, altitudes) = altitudes(route, AltitudeSource::Auto)?;
+    let points = project_points::<f64, T>(route)?;
+
+    let thermals = segments(route)?.into_iter()
+        .filter(|segment| segment.kind == SegmentKind::Circling)
+        .map(|segment| {
+            let (entry, exit) = (segment.start, segment.end);
+            let altitude_gain = i32::from(altitudes[exit]) - i32::from(altitudes[entry]);
+            // circling segments last at least `MIN_THERMAL_DURATION` seconds
+            let climb_rate = f64::from(altitude_gain) / f64::from(times[exit] - times[entry]);
+
+            // the centers of both halves of the thermal average out the circles
+            let middle = (entry + exit).div_ceil(2);
+            let (first, first_time) = center(&points[entry..middle], &times[entry..middle]);
+            let (second, second_time) = center(&points[middle..=exit], &times[middle..=exit]);
+
+            let (dx, dy) = (second.x - first.x, second.y - first.y);
+            let drift_direction = Some(dx.atan2(dy).to_degrees().rem_euclid(360.)).filter(|_| dx != 0. || dy != 0.);
+            let drift_speed = dx.hypot(dy) / (second_time - first_time) * 3600.;
+
+            Thermal { entry, exit, altitude_gain, climb_rate, drift_direction, drift_speed }
+        })
+        .collect();
+
+    Some(thermals)
+}
+
+/// Returns the absolute turn rate in degrees per second of every fix within
+/// `TURN_WINDOW` around it. The absolute heading changes are used, because
+/// low fix rates can not tell a left from a right turn.
+fn turn_rates(points: &[FlatPoint<f64>], times: &[u32]) -> Vec<f64> {
+    let turns = turns(points);
+    let (mut first, mut last) = (0, 0);
+    let mut sum = 0.;
+
+    (0..points.len())
+        .map(|i| {
+            while last < points.len() && times[last] <= times[i] + TURN_WINDOW / 2 {
+                sum += turns[last].abs();
+                last += 1;
+            }
+            while times[first] + TURN_WINDOW / 2 < times[i] {
+                sum -= turns[first].abs();
+                first += 1;
+            }
+
+            sum / f64::from(TURN_WINDOW)
+        })
+        .collect()
+}
+
+/// Merges consecutive fixes with the same state into segments
+fn to_segments(circling: &[bool]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    for (i, &circling) in circling.iter().enumerate() {
+        let kind = if circling { SegmentKind::Circling } else { SegmentKind::Cruising };
+        match segments.last_mut() {
+            Some(segment) if segment.kind == kind => segment.end = i,
+            _ => segments.push(Segment { kind, start: i, end: i }),
+        }
+    }
+    segments
+}
+
+/// Returns the mean position and time of the `points`
+fn center(points: &[FlatPoint<f64>], times: &[u32]) -> (FlatPoint<f64>, f64) {
+    let count = points.len() as f64;
+    let x = points.iter().map(|point| point.x).sum::<f64>() / count;
+    let y = points.iter().map(|point| point.y).sum::<f64>() / count;
+    let time = times.iter().map(|&time| f64::from(time)).sum::<f64>() / count;
+    (FlatPoint { x, y }, time)
+}
@@ -0,0 +1,61 @@
+use crate::Point;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Returns the time of every fix in seconds since midnight of the first
+/// day, continuing past `86400` if the flight crosses midnight.
+///
+/// Returns `None` if any of the fixes has no time.
+pub fn timeline<T: Point>(route: &[T]) -> Option<Vec<u32>> {
+    let times: Vec<u32> = route.iter().map(|fix| fix.time()).collect::<Option<_>>()?;
+    Some(continuous(&times))
+}
+
+/// Same as `timeline()`, but also returns `None` if any of the fixes is not
+/// later than the fix before it, see `track::sanitize()`
+pub(crate) fn increasing_timeline<T: Point>(route: &[T]) -> Option<Vec<u32>> {
+    timeline(route).filter(|times| times.windows(2).all(|pair| pair[0] < pair[1]))
+}
+
+/// Returns the indices of the fixes that are later than all fixes before
+/// them, so that a duplicate or out-of-order fix is skipped instead of the
+/// fixes after it. Fixes without time are kept. `track::sanitize()` can be
+/// used to move out-of-order fixes instead.
+pub(crate) fn increasing_indices<T: Point>(route: &[T]) -> Vec<usize> {
+    let mut last_time = None;
+    (0..route.len())
+        .filter(|&index| match route[index].time() {
+            Some(time) if last_time.is_some_and(|last_time| !is_later(time, last_time)) => false,
+            Some(time) => {
+                last_time = Some(time);
+                true
+            }
+            None => true,
+        })
+        .collect()
+}
+
+/// Returns `true` if `time` is later than `last_time`, taking midnight into
+/// account like `timeline()`
+pub(crate) fn is_later(time: u32, last_time: u32) -> bool {
+    let times = continuous(&[last_time, time]);
+    times[1] > times[0]
+}
+
+/// Adds a day to all `times` after a large jump backwards, see `timeline()`
+pub(crate) fn continuous(times: &[u32]) -> Vec<u32> {
+    let mut offset = 0;
+    let mut previous = None;
+
+    times.iter()
+        .map(|&time| {
+            // a large jump backwards means that we have passed midnight
+            if previous.is_some_and(|previous| previous > time + SECONDS_PER_DAY / 2) {
+                offset += SECONDS_PER_DAY;
+            }
+            previous = Some(time);
+
+            time + offset
+        })
+        .collect()
+}
@@ -0,0 +1,85 @@
+//! Sanitizing of tracks with missing, duplicate or out-of-order fixes,
+//! e.g. after logger restarts
+
+use crate::Point;
+use crate::time::continuous;
+
+/// Time inconsistency of a fix that was found by `sanitize()`. The indices
+/// refer to the original track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeIssue {
+    /// The fix has no time and was removed
+    MissingTime { index: usize },
+    /// The fix has the same time as an earlier fix and was removed
+    Duplicate { index: usize, time: u32 },
+    /// The fix is earlier than the fix before it and was moved
+    OutOfOrder { index: usize, time: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct SanitizedTrack<T> {
+    /// Fixes with strictly increasing times
+    pub fixes: Vec<T>,
+    /// Time inconsistencies in the order of the original track
+    pub issues: Vec<TimeIssue>,
+}
+
+/// Sorts the `fixes` by time, removes fixes without time and fixes with the
+/// same time as an earlier fix. The optimizers skip fixes that are not later
+/// than the fixes before them, so out-of-order fixes are only used after
+/// sanitizing.
+///
+/// Times continue past midnight as in `time::timeline()` and are reported in
+/// seconds since midnight of the first day. Tracks without any times are
+/// returned unchanged.
+pub fn sanitize<T: Point>(fixes: Vec<T>) -> SanitizedTrack<T> {
+    let mut issues = Vec::new();
+    if fixes.iter().all(|fix| fix.time().is_none()) {
+        return SanitizedTrack { fixes, issues };
+    }
+
+    let mut indices = Vec::new();
+    let mut timed = Vec::new();
+    for (index, fix) in fixes.into_iter().enumerate() {
+        if fix.time().is_some() {
+            indices.push(index);
+            timed.push(Some(fix));
+        } else {
+            issues.push(TimeIssue::MissingTime { index });
+        }
+    }
+
+    let times: Vec<u32> = timed.iter().filter_map(|fix| fix.as_ref()?.time()).collect();
+    let times = continuous(&times);
+
+    // the sort is stable, so the first of the fixes with the same time is kept
+    let mut order: Vec<usize> = (0..timed.len()).collect();
+    order.sort_by_key(|&i| times[i]);
+
+    let mut kept = vec![true; timed.len()];
+    for pair in order.windows(2) {
+        if times[pair[0]] == times[pair[1]] {
+            kept[pair[1]] = false;
+            issues.push(TimeIssue::Duplicate { index: indices[pair[1]], time: times[pair[1]] });
+        }
+    }
+
+    let mut latest = None;
+    for i in (0..timed.len()).filter(|&i| kept[i]) {
+        if latest.is_some_and(|latest| times[i] < latest) {
+            issues.push(TimeIssue::OutOfOrder { index: indices[i], time: times[i] });
+        }
+        latest = latest.max(Some(times[i]));
+    }
+
+    issues.sort_by_key(|issue| match *issue {
+        TimeIssue::MissingTime { index } | TimeIssue::Duplicate { index, .. } | TimeIssue::OutOfOrder { index, .. } => index,
+    });
+
+    let fixes = order.into_iter()
+        .filter(|&i| kept[i])
+        .map(|i| timed[i].take().unwrap())
+        .collect();
+
+    SanitizedTrack { fixes, issues }
+}
@@ -0,0 +1,404 @@
+use std::collections::BinaryHeap;
+use std::ops::Range;
+
+use flat_projection::FlatPoint;
+use log::trace;
+use ord_subset::OrdVar;
+
+use crate::float::Float;
+
+/// Maximum number of points in a leaf node of the `BoxTree`
+const LEAF_SIZE: usize = 8;
+
+/// Closest pair of points as `(distance, first index, second index)`
+pub type ClosestPair<F = f32> = (F, usize, usize);
+
+/// Axis-aligned bounding box on the flat surface
+#[derive(Debug, Clone, Copy)]
+pub struct BBox<F = f32> {
+    min_x: F,
+    min_y: F,
+    max_x: F,
+    max_y: F,
+}
+
+impl<F: Float> BBox<F> {
+    fn from_points(points: &[FlatPoint<F>]) -> Self {
+        points.iter().fold(BBox {
+            min_x: F::infinity(),
+            min_y: F::infinity(),
+            max_x: F::neg_infinity(),
+            max_y: F::neg_infinity(),
+        }, |bbox, p| BBox {
+            min_x: bbox.min_x.min(p.x),
+            min_y: bbox.min_y.min(p.y),
+            max_x: bbox.max_x.max(p.x),
+            max_y: bbox.max_y.max(p.y),
+        })
+    }
+
+    fn merge(&self, other: &BBox<F>) -> Self {
+        BBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    /// Length of the diagonal in kilometers
+    pub fn size(&self) -> F {
+        (self.max_x - self.min_x).hypot(self.max_y - self.min_y)
+    }
+
+    /// Lower bound for the distance between any point in `self` and any point in `other`
+    pub fn min_distance(&self, other: &BBox<F>) -> F {
+        let dx = (other.min_x - self.max_x).max(self.min_x - other.max_x).max(F::zero());
+        let dy = (other.min_y - self.max_y).max(self.min_y - other.max_y).max(F::zero());
+        dx.hypot(dy)
+    }
+
+    /// Upper bound for the distance between any point in `self` and any point in `other`
+    pub fn max_distance(&self, other: &BBox<F>) -> F {
+        let dx = (other.max_x - self.min_x).max(self.max_x - other.min_x);
+        let dy = (other.max_y - self.min_y).max(self.max_y - other.min_y);
+        dx.hypot(dy)
+    }
+
+    /// Corner of the bounding box that is farthest away from `point`. No
+    /// point in the box is farther away from `point` than this corner, even
+    /// with rounding errors.
+    fn farthest_corner(&self, point: &FlatPoint<F>) -> FlatPoint<F> {
+        let x = if (point.x - self.min_x).abs() >= (point.x - self.max_x).abs() { self.min_x } else { self.max_x };
+        let y = if (point.y - self.min_y).abs() >= (point.y - self.max_y).abs() { self.min_y } else { self.max_y };
+        FlatPoint { x, y }
+    }
+}
+
+/// Largest value with the index of the point as `(value, index)`
+pub type Farthest<F = f32> = (F, usize);
+
+#[derive(Debug)]
+pub struct Node<F = f32> {
+    /// Index of the first point in this node
+    pub start: usize,
+    /// Index after the last point in this node
+    pub end: usize,
+    pub bbox: BBox<F>,
+    pub children: Option<(usize, usize)>,
+}
+
+impl<F> Node<F> {
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_none()
+    }
+}
+
+/// Problem that can be solved by `BoxTree::search_tuples()`
+pub trait TupleSearch<F = f32> {
+    type Solution;
+
+    /// Number of points in a solution
+    fn size(&self) -> usize;
+
+    /// Returns an upper bound for the value of all solutions in the given
+    /// nodes, or `None` if the nodes can not contain a valid solution that is
+    /// better than `best_value`.
+    fn upper_bound(&mut self, nodes: &[&Node<F>], best_value: F) -> Option<F>;
+
+    /// Exhaustively searches the given leaf nodes for a valid solution that
+    /// is better than `best_value` and returns it together with its value
+    fn search_leaves(&mut self, nodes: &[&Node<F>], best_value: F) -> Option<(F, Self::Solution)>;
+}
+
+/// Binary tree of bounding boxes over consecutive index ranges of a track.
+///
+/// The tree is used by the branch-and-bound searches to quickly discard
+/// large parts of the track that can not contain a better solution.
+pub struct BoxTree<F = f32> {
+    nodes: Vec<Node<F>>,
+}
+
+impl<F: Float> BoxTree<F> {
+    pub fn new(points: &[FlatPoint<F>]) -> Self {
+        let mut nodes = Vec::with_capacity(2 * points.len() / LEAF_SIZE + 1);
+        if !points.is_empty() {
+            build_node(&mut nodes, points, 0, points.len());
+        }
+
+        BoxTree { nodes }
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    pub fn node(&self, id: usize) -> &Node<F> {
+        &self.nodes[id]
+    }
+
+    fn nodes(&self, ids: &[usize]) -> Vec<&Node<F>> {
+        ids.iter().map(|&id| self.node(id)).collect()
+    }
+
+    /// Branch-and-bound search over tuples of nodes
+    ///
+    /// Every tuple of nodes describes the set of all solutions that have
+    /// their first point in the first node, the second in the second node,
+    /// and so on. The tuples are processed in the order of their upper bound
+    /// and are split until they contain only leaf nodes, which are then
+    /// searched exhaustively. As soon as the upper bound of the next tuple
+    /// is not better than the best solution found so far the search is
+    /// finished.
+    pub fn search_tuples<S: TupleSearch<F>>(&self, search: &mut S) -> Option<S::Solution> {
+        let mut best: Option<(F, S::Solution)> = None;
+        let mut queue = BinaryHeap::new();
+
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let root = vec![self.root(); search.size()];
+        if let Some(upper_bound) = self.tuple_upper_bound(search, &root, F::zero()) {
+            queue.push((OrdVar::new(upper_bound), root));
+        }
+
+        while let Some((upper_bound, ids)) = queue.pop() {
+            let best_value = best.as_ref().map_or(F::zero(), |it| it.0);
+            if *upper_bound <= best_value {
+                break;
+            }
+
+            let split = (0..ids.len())
+                .filter(|&i| !self.node(ids[i]).is_leaf())
+                .max_by_key(|&i| OrdVar::new(self.node(ids[i]).bbox.size()));
+
+            match split {
+                None => {
+                    if let Some(solution) = search.search_leaves(&self.nodes(&ids), best_value) {
+                        trace!("-- New best solution: {:.3}", solution.0);
+                        best = Some(solution);
+                    }
+                }
+                Some(i) => {
+                    let (left, right) = self.node(ids[i]).children.unwrap();
+                    for &child in &[left, right] {
+                        let mut child_ids = ids.clone();
+                        child_ids[i] = child;
+
+                        if let Some(upper_bound) = self.tuple_upper_bound(search, &child_ids, best_value) {
+                            queue.push((OrdVar::new(upper_bound), child_ids));
+                        }
+                    }
+                }
+            }
+        }
+
+        best.map(|(_, solution)| solution)
+    }
+
+    fn tuple_upper_bound<S: TupleSearch<F>>(&self, search: &mut S, ids: &[usize], best_value: F) -> Option<F> {
+        let nodes = self.nodes(ids);
+
+        // the points have to be in chronological order
+        if nodes.windows(2).any(|pair| pair[0].start + 1 >= pair[1].end) {
+            return None;
+        }
+
+        search.upper_bound(&nodes, best_value)
+    }
+
+    /// Returns the largest of the `values` in every node, as needed by
+    /// `farthest()`
+    pub fn node_maxima(&self, values: &[F]) -> Vec<F> {
+        let mut maxima = vec![F::neg_infinity(); self.nodes.len()];
+
+        // children are always stored after their parent
+        for (id, node) in self.nodes.iter().enumerate().rev() {
+            maxima[id] = match node.children {
+                Some((left, right)) => maxima[left].max(maxima[right]),
+                None => values[node.start..node.end].iter().fold(F::neg_infinity(), |max, &value| max.max(value)),
+            };
+        }
+
+        maxima
+    }
+
+    /// Finds the index `j` in `range` with the largest value of
+    /// `values[j] + point.distance(&points[j])` and returns `(value, j)`.
+    /// Points with a value of negative infinity are ignored and ties are
+    /// resolved in favor of the larger index. The search is faster if the
+    /// `hint` is already close to the result, e.g. the result for a
+    /// neighboring point.
+    pub fn farthest(
+        &self,
+        points: &[FlatPoint<F>],
+        values: &[F],
+        maxima: &[F],
+        point: &FlatPoint<F>,
+        range: Range<usize>,
+        hint: Option<usize>,
+    ) -> Option<Farthest<F>> {
+        let mut best = hint
+            .filter(|&j| range.contains(&j) && values[j] > F::neg_infinity())
+            .map(|j| (values[j] + point.distance(&points[j]), j));
+
+        if !self.nodes.is_empty() && range.start < range.end {
+            let query = FarthestQuery { points, values, maxima, point, range };
+            self.visit_farthest(&query, self.root(), &mut best);
+        }
+        best
+    }
+
+    fn visit_farthest(&self, query: &FarthestQuery<F>, id: usize, best: &mut Option<Farthest<F>>) {
+        let node = self.node(id);
+        if node.start >= query.range.end || node.end <= query.range.start {
+            return;
+        }
+
+        let upper_bound = query.upper_bound(id, node);
+        // ties are resolved in favor of the larger index
+        let dominated = |(value, index): Farthest<F>| {
+            upper_bound < value || (upper_bound == value && node.end <= index + 1)
+        };
+        if upper_bound == F::neg_infinity() || best.is_some_and(dominated) {
+            return;
+        }
+
+        let (left, right) = match node.children {
+            Some(children) => children,
+            None => {
+                for j in node.start.max(query.range.start)..node.end.min(query.range.end) {
+                    if query.values[j] == F::neg_infinity() {
+                        continue;
+                    }
+
+                    let value = query.values[j] + query.point.distance(&query.points[j]);
+                    if best.is_none_or(|(best_value, index)| value > best_value || (value == best_value && j > index)) {
+                        *best = Some((value, j));
+                    }
+                }
+                return;
+            }
+        };
+
+        // visit the more promising child first to find good solutions early
+        let upper_bound = |id: usize| query.upper_bound(id, self.node(id));
+        let children = if upper_bound(left) > upper_bound(right) { [left, right] } else { [right, left] };
+
+        for &child in &children {
+            self.visit_farthest(query, child, best);
+        }
+    }
+
+    /// Finds the closest pair of points `(s, f)` with `s <= prefix_end` and
+    /// `f >= suffix_start` and returns `(distance, s, f)`
+    pub fn closest_pair(
+        &self,
+        points: &[FlatPoint<F>],
+        prefix_end: usize,
+        suffix_start: usize,
+    ) -> Option<ClosestPair<F>> {
+        let mut best = None;
+        if !self.nodes.is_empty() && suffix_start < points.len() {
+            self.visit_pair(points, self.root(), self.root(), prefix_end, suffix_start, &mut best);
+        }
+        best
+    }
+
+    fn visit_pair(
+        &self,
+        points: &[FlatPoint<F>],
+        x: usize,
+        y: usize,
+        prefix_end: usize,
+        suffix_start: usize,
+        best: &mut Option<ClosestPair<F>>,
+    ) {
+        let node_x = self.node(x);
+        let node_y = self.node(y);
+        if node_x.start > prefix_end || node_y.end <= suffix_start {
+            return;
+        }
+
+        let min_distance = node_x.bbox.min_distance(&node_y.bbox);
+        if best.is_some_and(|(distance, _, _)| min_distance >= distance) {
+            return;
+        }
+
+        let split_x = match (node_x.children, node_y.children) {
+            (None, None) => {
+                let s_end = node_x.end.min(prefix_end + 1);
+                let f_start = node_y.start.max(suffix_start);
+                for s in node_x.start..s_end {
+                    for f in f_start..node_y.end {
+                        let distance = points[s].distance(&points[f]);
+                        if best.is_none_or(|(best_distance, _, _)| distance < best_distance) {
+                            *best = Some((distance, s, f));
+                        }
+                    }
+                }
+                return;
+            }
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (Some(_), Some(_)) => node_x.bbox.size() >= node_y.bbox.size(),
+        };
+
+        let mut pairs = if split_x {
+            let (left, right) = node_x.children.unwrap();
+            [(left, y), (right, y)]
+        } else {
+            let (left, right) = node_y.children.unwrap();
+            [(x, left), (x, right)]
+        };
+
+        // visit the closer pair first to find good solutions early
+        let min_distance = |(x, y): (usize, usize)| self.node(x).bbox.min_distance(&self.node(y).bbox);
+        if min_distance(pairs[1]) < min_distance(pairs[0]) {
+            pairs.swap(0, 1);
+        }
+
+        for &(x, y) in &pairs {
+            self.visit_pair(points, x, y, prefix_end, suffix_start, best);
+        }
+    }
+}
+
+/// Parameters of `BoxTree::farthest()`
+struct FarthestQuery<'a, F> {
+    points: &'a [FlatPoint<F>],
+    values: &'a [F],
+    maxima: &'a [F],
+    point: &'a FlatPoint<F>,
+    range: Range<usize>,
+}
+
+impl<F: Float> FarthestQuery<'_, F> {
+    /// Upper bound for the value of all points in the node with the given `id`
+    fn upper_bound(&self, id: usize, node: &Node<F>) -> F {
+        self.maxima[id] + self.point.distance(&node.bbox.farthest_corner(self.point))
+    }
+}
+
+fn build_node<F: Float>(nodes: &mut Vec<Node<F>>, points: &[FlatPoint<F>], start: usize, end: usize) -> usize {
+    let id = nodes.len();
+
+    if end - start <= LEAF_SIZE {
+        let bbox = BBox::from_points(&points[start..end]);
+        nodes.push(Node { start, end, bbox, children: None });
+        return id;
+    }
+
+    // reserve the slot so that the parent is always stored before its children
+    nodes.push(Node { start, end, bbox: BBox::from_points(&[]), children: None });
+
+    let mid = start + (end - start) / 2;
+    let left = build_node(nodes, points, start, mid);
+    let right = build_node(nodes, points, mid, end);
+
+    nodes[id].bbox = nodes[left].bbox.merge(&nodes[right].bbox);
+    nodes[id].children = Some((left, right));
+
+    id
+}
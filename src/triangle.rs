@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use failure::Error;
+use flat_projection::FlatPoint;
+use log::debug;
+
+use crate::Point;
+use crate::flat::project_points;
+use crate::float::Float;
+use crate::olc::{check_track, DistanceFormula, Precision};
+use crate::tree::{BoxTree, ClosestPair, Node, TupleSearch};
+
+/// Maximum distance between start and finish relative to the triangle distance (OLC rules)
+const MAX_CLOSING_RATIO: f64 = 0.2;
+
+/// Triangle distance in kilometers from which on the 25%/45% leg rule applies (OLC rules)
+const LARGE_TRIANGLE_THRESHOLD: f64 = 500.;
+
+/// Number of fixes that the three turnpoints need at least
+const MIN_FIXES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriangleKind {
+    /// Every leg has to satisfy the leg ratio rules of the FAI
+    Fai,
+    /// No constraints on the leg lengths (e.g. DMSt or WeGlide flat triangles)
+    Flat,
+}
+
+#[derive(Debug, Clone)]
+pub struct TriangleOptions {
+    pub kind: TriangleKind,
+    /// Maximum distance between start and finish as a fraction of the
+    /// triangle distance
+    pub max_closing_ratio: f64,
+    /// Triangle distance in kilometers from which on every leg has to be at
+    /// least 25% and at most 45% of the triangle distance instead of at
+    /// least 28%. The OLC uses 500 km, the FAI Sporting Code 750 km.
+    /// Only used for `TriangleKind::Fai`.
+    pub large_triangle_threshold: f64,
+    /// Formula for the `distance` of the result
+    pub distance_formula: DistanceFormula,
+    pub precision: Precision,
+}
+
+impl Default for TriangleOptions {
+    fn default() -> Self {
+        TriangleOptions {
+            kind: TriangleKind::Fai,
+            max_closing_ratio: MAX_CLOSING_RATIO,
+            large_triangle_threshold: LARGE_TRIANGLE_THRESHOLD,
+            distance_formula: DistanceFormula::Haversine,
+            precision: Precision::Single,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TriangleResult {
+    /// Index of the fix where the closed part of the flight starts
+    pub start: usize,
+    /// Indices of the three triangle vertices
+    pub turnpoints: [usize; 3],
+    /// Index of the fix where the closed part of the flight ends
+    pub finish: usize,
+    /// Sum of the three triangle legs in kilometers, calculated in double
+    /// precision
+    pub distance: f64,
+    /// Distance between `start` and `finish` in kilometers
+    pub closing_distance: f64,
+}
+
+/// Finds the largest FAI triangle in the `route`, or `None` if the flight
+/// does not contain a closed FAI triangle.
+pub fn optimize<T: Point>(route: &[T]) -> Result<Option<TriangleResult>, Error> {
+    optimize_with(route, &TriangleOptions::default())
+}
+
+/// Finds the largest flat triangle in the `route`, or `None` if the flight
+/// does not contain a closed triangle.
+pub fn optimize_flat<T: Point>(route: &[T]) -> Result<Option<TriangleResult>, Error> {
+    optimize_with(route, &TriangleOptions { kind: TriangleKind::Flat, ..TriangleOptions::default() })
+}
+
+pub fn optimize_with<T: Point>(route: &[T], options: &TriangleOptions) -> Result<Option<TriangleResult>, Error> {
+    let indices = check_track(route, MIN_FIXES)?;
+    let fixes: Vec<&T> = indices.iter().map(|&index| &route[index]).collect();
+
+    let solution = match options.precision {
+        Precision::Single => search::<f32, &T>(&fixes, options),
+        Precision::Double => search::<f64, &T>(&fixes, options),
+    };
+
+    let solution = match solution {
+        Some(solution) => solution,
+        None => {
+            debug!("No valid triangle found");
+            return Ok(None);
+        }
+    };
+
+    let (start, finish) = (indices[solution.start], indices[solution.finish]);
+    let turnpoints = solution.turnpoints.map(|index| indices[index]);
+
+    let formula = options.distance_formula;
+    let [tp1, tp2, tp3] = turnpoints;
+    let distance = formula.distance(&route[tp1], &route[tp2]) +
+        formula.distance(&route[tp2], &route[tp3]) +
+        formula.distance(&route[tp3], &route[tp1]);
+
+    let closing_distance = formula.distance(&route[start], &route[finish]);
+    debug!("Solution: {:?} ({:.3} km)", turnpoints, distance);
+
+    Ok(Some(TriangleResult { start, turnpoints, finish, distance, closing_distance }))
+}
+
+/// Finds the largest triangle on the flat surface with the precision of `F`
+fn search<F: Float, T: Point>(route: &[T], options: &TriangleOptions) -> Option<Solution> {
+    debug!("Converting {} points to flat points", route.len());
+    let flat_points = project_points::<F, T>(route)?;
+
+    debug!("Building bounding box tree");
+    let tree = BoxTree::new(&flat_points);
+
+    debug!("Searching for best triangle");
+    let mut search = Search { points: &flat_points, tree: &tree, options, closing_cache: HashMap::new() };
+    tree.search_tuples(&mut search)
+}
+
+/// Solution of the search on the flat surface
+#[derive(Debug)]
+struct Solution {
+    start: usize,
+    turnpoints: [usize; 3],
+    finish: usize,
+}
+
+/// Search for the largest triangle via `BoxTree::search_tuples()`
+struct Search<'a, F> {
+    points: &'a [FlatPoint<F>],
+    tree: &'a BoxTree<F>,
+    options: &'a TriangleOptions,
+    closing_cache: HashMap<(usize, usize), Option<ClosestPair<F>>>,
+}
+
+impl<F: Float> TupleSearch<F> for Search<'_, F> {
+    type Solution = Solution;
+
+    fn size(&self) -> usize {
+        3
+    }
+
+    fn upper_bound(&mut self, nodes: &[&Node<F>], best_distance: F) -> Option<F> {
+        let (a, b, c) = (nodes[0], nodes[1], nodes[2]);
+        let legs = [(&a.bbox, &b.bbox), (&b.bbox, &c.bbox), (&c.bbox, &a.bbox)];
+        let min_legs = legs.iter().map(|(x, y)| x.min_distance(y)).collect::<Vec<_>>();
+        let max_legs = legs.iter().map(|(x, y)| x.max_distance(y)).collect::<Vec<_>>();
+
+        let max_distance: F = max_legs.iter().cloned().sum();
+        if max_distance <= best_distance {
+            return None;
+        }
+
+        if self.options.kind == TriangleKind::Fai {
+            // every leg needs to reach the minimum leg ratio of the FAI rule
+            let min_distance: F = min_legs.iter().cloned().sum();
+            let min_ratio = F::from_f64(if max_distance >= self.threshold() { 0.25 } else { 0.28 });
+            let fai_possible = (0..3)
+                .all(|i| (F::one() - min_ratio) * max_legs[i] >= min_ratio * (min_distance - min_legs[i]));
+            if !fai_possible {
+                return None;
+            }
+        }
+
+        let max_closing = self.max_closing_ratio() * max_distance;
+        match self.closing(a.end - 1, c.start) {
+            Some((closing, _, _)) if closing <= max_closing => Some(max_distance),
+            _ => None,
+        }
+    }
+
+    fn search_leaves(&mut self, nodes: &[&Node<F>], best_distance: F) -> Option<(F, Solution)> {
+        let (a, b, c) = (nodes[0], nodes[1], nodes[2]);
+        let mut best = None;
+        let mut best_distance = best_distance;
+
+        for tp1 in a.start..a.end {
+            for tp2 in (b.start..b.end).filter(|&tp2| tp2 > tp1) {
+                let leg1 = self.points[tp1].distance(&self.points[tp2]);
+
+                for tp3 in (c.start..c.end).filter(|&tp3| tp3 > tp2) {
+                    let leg2 = self.points[tp2].distance(&self.points[tp3]);
+                    let leg3 = self.points[tp3].distance(&self.points[tp1]);
+
+                    let distance = leg1 + leg2 + leg3;
+                    if distance <= best_distance || !self.is_valid([leg1, leg2, leg3], distance) {
+                        continue;
+                    }
+
+                    let max_closing = self.max_closing_ratio() * distance;
+                    if let Some((closing, start, finish)) = self.closing(tp1, tp3) {
+                        if closing <= max_closing {
+                            best_distance = distance;
+                            best = Some((distance, Solution { start, turnpoints: [tp1, tp2, tp3], finish }));
+                        }
+                    }
+                }
+            }
+        }
+
+        best
+    }
+}
+
+impl<F: Float> Search<'_, F> {
+    /// Checks the leg ratios of the FAI rule
+    fn is_valid(&self, legs: [F; 3], distance: F) -> bool {
+        if self.options.kind == TriangleKind::Flat {
+            return true;
+        }
+
+        let shortest = legs.iter().cloned().fold(F::infinity(), F::min);
+        let longest = legs.iter().cloned().fold(F::zero(), F::max);
+
+        if distance < self.threshold() {
+            shortest >= F::from_f64(0.28) * distance
+        } else {
+            shortest >= F::from_f64(0.25) * distance && longest <= F::from_f64(0.45) * distance
+        }
+    }
+
+    fn threshold(&self) -> F {
+        F::from_f64(self.options.large_triangle_threshold)
+    }
+
+    fn max_closing_ratio(&self) -> F {
+        F::from_f64(self.options.max_closing_ratio)
+    }
+
+    /// Returns the closest pair of fixes `(distance, start, finish)` with
+    /// `start <= tp1` and `finish >= tp3`
+    fn closing(&mut self, tp1: usize, tp3: usize) -> Option<ClosestPair<F>> {
+        let (tree, points) = (self.tree, self.points);
+        *self.closing_cache.entry((tp1, tp3))
+            .or_insert_with(|| tree.closest_pair(points, tp1, tp3))
+    }
+}
@@ -0,0 +1,78 @@
+#[macro_use]
+extern crate assert_approx_eq;
+
+extern crate aeroscore;
+
+use aeroscore::{engine, olc};
+use aeroscore::engine::EngineSource;
+use aeroscore::igc::{Date, Flight};
+use aeroscore::olc::{EngineRule, OptimizeOptions};
+
+#[test]
+fn engine_runs_for_87i_qqk() {
+    // the ENL sensor of this logger reports a running engine all the time
+    let fixes = Flight::parse(include_str!("fixtures/87ilqqk1.igc")).fixes;
+    let (source, runs) = engine::engine_runs(&fixes).unwrap();
+    assert_eq!(source, EngineSource::Climb);
+    assert_eq!(runs, vec![5..128]);
+
+    let options = OptimizeOptions { engine_rule: EngineRule::AfterLastRun, ..OptimizeOptions::default() };
+    let result = olc::optimize_with(&fixes, &options).unwrap();
+    assert_approx_eq!(result.distance, 779.26, 0.1);
+    assert!(result.path[0] >= 128);
+}
+
+#[test]
+fn engine_runs_from_enl() {
+    let mut fixes = Flight::parse(include_str!("fixtures/2017-08-14-fla-6ng-01.igc")).fixes;
+    for (i, fix) in fixes.iter_mut().enumerate() {
+        fix.enl = Some(if (3000..3100).contains(&i) { 900 } else { 50 });
+    }
+
+    let (source, runs) = engine::engine_runs(&fixes).unwrap();
+    assert_eq!(source, EngineSource::EngineNoiseLevel);
+    assert_eq!(runs, vec![3000..3100]);
+    assert_eq!(engine::powerless_segments(fixes.len(), &runs), vec![0..3000, 3100..fixes.len()]);
+
+    let options = OptimizeOptions { engine_rule: EngineRule::PowerlessSegments, ..OptimizeOptions::default() };
+    let result = olc::optimize_with(&fixes, &options).unwrap();
+    assert!(result.path.iter().all(|&i| i < 3000) || result.path.iter().all(|&i| i >= 3100));
+    assert_approx_eq!(result.distance, 297.71, 0.1);
+
+    let options = OptimizeOptions { engine_rule: EngineRule::AfterLastRun, ..OptimizeOptions::default() };
+    let result = olc::optimize_with(&fixes, &options).unwrap();
+    assert!(result.path[0] >= 3100);
+    assert_approx_eq!(result.distance, 236.68, 0.1);
+}
+
+#[test]
+fn read_igc_file() {
+    let flight = Flight::read(&include_bytes!("fixtures/87ilqqk1.igc")[..]).unwrap();
+
+    assert_eq!(flight.header.date, Some(Date { year: 2018, month: 7, day: 18 }));
+    assert_eq!(flight.header.pilot.as_deref(), Some("TORBEN RAATZ"));
+    assert_eq!(flight.header.glider_type.as_deref(), Some("Std Libel"));
+    assert_eq!(flight.header.glider_id, None);
+    assert_eq!(flight.header.logger_type.as_deref(), Some("LX navigation, LX Era 80"));
+    assert_eq!(flight.extensions, vec!["FXA", "SIU", "GSP", "ENL", "GFO"]);
+
+    let fix = &flight.fixes[0];
+    assert_eq!(fix.extensions.len(), 5);
+    assert_eq!(fix.enl, fix.extensions[3].parse().ok());
+    assert_eq!(fix.mop, None);
+    assert!(flight.fixes.iter().all(|fix| fix.enl.is_some()));
+
+    let task = flight.task.unwrap();
+    assert_eq!(task.takeoff.unwrap().name.as_deref(), Some("AACHEN MERZ"));
+    let names: Vec<_> = task.points.iter().map(|point| point.name.as_deref().unwrap()).collect();
+    assert_eq!(names, vec!["AACHEN MERZ", "DILLINGEN", "DIEMELSEE S", "AACHEN MERZ"]);
+    assert_approx_eq!(task.points[1].latitude, 49.386_1, 1e-4);
+    assert_approx_eq!(task.points[1].longitude, 6.748_6, 1e-4);
+
+    let flight = Flight::parse(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"));
+    assert_eq!(flight.header.competition_id.as_deref(), Some("TH"));
+    let task = flight.task.unwrap();
+    assert_eq!(task.name.as_deref(), Some("Task"));
+    assert_eq!(task.takeoff, None);
+    assert_eq!(task.points.len(), 4);
+}
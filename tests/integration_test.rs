@@ -2,19 +2,59 @@
 extern crate assert_approx_eq;
 
 extern crate aeroscore;
-extern crate igc;
 
-use aeroscore::olc;
-use aeroscore::olc::OptimizationResult;
-use igc::util::Time;
+use aeroscore::{altitude, engine, flat, geodesic, olc, out_and_return, outliers, phases, simplify, thermals, time, track, triangle};
+use aeroscore::altitude::AltitudeSource;
+use aeroscore::engine::EngineSource;
+use aeroscore::haversine::haversine_distance_f64;
+use aeroscore::igc::{Fix, Flight};
+use aeroscore::outliers::{Outlier, OutlierOptions, OutlierReason};
+use aeroscore::phases::LaunchMethod;
+use aeroscore::thermals::SegmentKind;
+use aeroscore::track::TimeIssue;
+use aeroscore::olc::{AltitudeRule, CancellationToken, Cancelled, DistanceFormula, EngineRule, LiveOptimizer, OptimizationResult, OptimizeError, OptimizeOptions, Precision, Progress};
 
+#[derive(Debug, Clone, PartialEq)]
 struct Point {
+    time: u32,
+    latitude: f64,
+    longitude: f64,
+    altitude: i16,
+    gnss_altitude: i16,
+}
+
+impl aeroscore::Point for Point {
+    fn latitude(&self) -> f32 {
+        self.latitude as f32
+    }
+    fn longitude(&self) -> f32 {
+        self.longitude as f32
+    }
+    fn latitude_f64(&self) -> f64 {
+        self.latitude
+    }
+    fn longitude_f64(&self) -> f64 {
+        self.longitude
+    }
+    fn altitude(&self) -> i16 {
+        self.altitude
+    }
+    fn gnss_altitude(&self) -> Option<i16> {
+        Some(self.gnss_altitude)
+    }
+    fn time(&self) -> Option<u32> {
+        Some(self.time)
+    }
+}
+
+/// Fix without GNSS altitude and time
+struct Position {
     latitude: f32,
     longitude: f32,
     altitude: i16,
 }
 
-impl aeroscore::Point for Point {
+impl aeroscore::Point for Position {
     fn latitude(&self) -> f32 {
         self.latitude
     }
@@ -28,7 +68,7 @@ impl aeroscore::Point for Point {
 
 #[test]
 fn distance_for_78e_6ng() {
-    let release = Time::from_hms(10, 28, 05);
+    let release = hms(10, 28, 5);
     let result = run_test(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release);
     assert_approx_eq!(result.distance, 501.3, 0.1);
     assert_eq!(result.path, vec![197, 1225, 2080, 3492, 4946, 5504, 6104]);
@@ -36,39 +76,791 @@ fn distance_for_78e_6ng() {
 
 #[test]
 fn distance_for_87i_qqk() {
-    let release = Time::from_hms(09, 02, 05);
+    let release = hms(9, 2, 5);
     let result = run_test(include_str!("fixtures/87ilqqk1.igc"), release);
     assert_approx_eq!(result.distance, 780.42, 0.1);
     assert_eq!(result.path, vec![1, 1129, 1666, 4348, 6070, 6681, 7194]);
+    assert_eq!(result.start_time, Some(32530));
+    assert_eq!(result.duration(), Some(37167));
+}
+
+#[test]
+fn distance_for_87i_qqk_across_antimeridian() {
+    let release = hms(9, 2, 5);
+    let result = run_test(include_str!("fixtures/87ilqqk1-antimeridian.igc"), release);
+    assert_approx_eq!(result.distance, 780.42, 0.1);
+    assert_eq!(result.path, vec![1, 1129, 1666, 4348, 6070, 6681, 7194]);
 }
 
 #[test]
-#[ignore]
 fn distance_for_99b_7r9() {
-    let release = Time::from_hms(16, 54, 06);
+    // the previously expected 197.14 km path [106, 5041, 5927, 6388, 6731,
+    // 10294, 15398] loses 14282 m of height between start and finish, so it
+    // was never valid with the 1000 m rule
+    let release = hms(16, 54, 6);
     let result = run_test(include_str!("fixtures/99bv7r92.igc"), release);
-    assert_approx_eq!(result.distance, 197.14, 0.1);
-    assert_eq!(result.path, vec![106, 5041, 5927, 6388, 6731, 10294, 15398]);
+    assert_approx_eq!(result.distance, 139.73, 0.1);
+    assert_eq!(result.path, vec![6707, 7447, 7562, 10292, 10563, 11519, 12019]);
 }
 
-fn run_test(file: &str, release: Time) -> OptimizationResult {
-    env_logger::try_init().ok();
+#[test]
+fn distance_for_78e_6ng_equals_distance_matrix() {
+    let release = hms(10, 28, 5);
+    let fixes: Vec<_> = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release).into_iter()
+        .step_by(10)
+        .collect();
+
+    // reference solution from a full distance matrix, which the optimizer
+    // no longer keeps in memory
+    let flat_points = flat::to_flat_points(&fixes).unwrap();
+    let matrix: Vec<Vec<f32>> = flat_points.iter()
+        .map(|p1| flat_points.iter().map(|p2| p1.distance(p2)).collect())
+        .collect();
+
+    // best path with the current number of legs that finishes at every point
+    let mut best: Vec<(f32, Vec<usize>)> = (0..fixes.len()).map(|j| (0., vec![j])).collect();
+    for _ in 0..6 {
+        best = (0..fixes.len())
+            .map(|j| (0..j)
+                .map(|i| (best[i].0 + matrix[i][j], i))
+                .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                .map_or((f32::NEG_INFINITY, vec![j]), |(distance, i)| (distance, [&best[i].1[..], &[j]].concat())))
+            .collect();
+    }
+    let (_, expected) = best.into_iter().max_by(|a, b| a.0.partial_cmp(&b.0).unwrap()).unwrap();
+
+    let options = OptimizeOptions { altitude_rule: AltitudeRule::Disabled, ..OptimizeOptions::default() };
+    let result = olc::optimize_with(&fixes, &options).unwrap();
+    assert_eq!(result.path, expected);
+}
+
+#[test]
+fn distance_for_87i_qqk_with_3_legs() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let result = olc::optimize_with(&fixes, &OptimizeOptions { legs: 3, ..OptimizeOptions::default() }).unwrap();
+    assert_approx_eq!(result.distance, 624.65, 0.1);
+    assert_eq!(result.path, vec![4, 1667, 4348, 6071]);
+}
+
+#[test]
+fn distance_for_78e_6ng_with_altitude_rules() {
+    let release = hms(10, 28, 5);
+    let fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release);
+
+    let optimize = |altitude_rule| {
+        let options = OptimizeOptions { altitude_rule, ..OptimizeOptions::default() };
+        olc::optimize_with(&fixes, &options).unwrap()
+    };
+
+    let result = optimize(AltitudeRule::Disabled);
+    assert_approx_eq!(result.distance, 501.3, 0.1);
+    assert_eq!(result.height_loss, 550);
+
+    let result = optimize(AltitudeRule::MaxHeightLoss(500));
+    assert_approx_eq!(result.distance, 501.12, 0.1);
+    assert!(result.height_loss <= 500);
+
+    let result = optimize(AltitudeRule::Penalty { max_height_loss: 500, penalty_per_meter: 0.001 });
+    assert_approx_eq!(result.penalty, 0.039, 0.001);
+    assert_approx_eq!(result.distance, 501.26, 0.1);
+    assert_eq!(result.path, vec![197, 1225, 2080, 3492, 4946, 5504, 6103]);
+}
+
+#[test]
+fn distance_for_87i_qqk_with_broken_pressure_sensor() {
+    let release = hms(9, 2, 5);
+    let mut fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    fixes.iter_mut().for_each(|fix| fix.altitude = 0);
+
+    let result = olc::optimize(&fixes).unwrap();
+    assert_eq!(result.altitude_source, AltitudeSource::Gnss);
+    assert_approx_eq!(result.distance, 780.05, 0.1);
+}
+
+#[test]
+fn distance_for_87i_qqk_on_wgs84() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let options = OptimizeOptions { distance_formula: DistanceFormula::Wgs84, ..OptimizeOptions::default() };
+    let result = olc::optimize_with(&fixes, &options).unwrap();
+    assert_approx_eq!(result.distance, 781.80, 0.1);
+    assert_eq!(result.path, vec![1, 1129, 1666, 4348, 6070, 6681, 7194]);
+}
+
+#[test]
+fn distance_for_99b_7r9_with_double_precision() {
+    let release = hms(16, 54, 6);
+    let fixes = load_fixes(include_str!("fixtures/99bv7r92.igc"), release);
+    let options = OptimizeOptions { precision: Precision::Double, ..OptimizeOptions::default() };
+    let result = olc::optimize_with(&fixes, &options).unwrap();
+    assert_approx_eq!(result.distance, 139.73, 0.1);
+    assert_eq!(result.path, vec![6707, 7447, 7562, 10292, 10563, 11519, 12019]);
+
+    let mut live = LiveOptimizer::new(options).unwrap();
+    assert!(fixes.into_iter().all(|fix| live.push(fix)));
+    assert_eq!(live.current_best().unwrap().unwrap().path, result.path);
+    assert_eq!(live.finish().unwrap().path, result.path);
+}
+
+#[test]
+fn flight_phases_for_78e_6ng() {
+    let fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), hms(0, 0, 0));
+    let phases = phases::detect(&fixes).unwrap();
+    assert_eq!(phases.launch, LaunchMethod::Aerotow);
+    assert_eq!(fixes[phases.takeoff].time, hms(10, 24, 9));
+    assert_eq!(fixes[phases.release].time, hms(10, 27, 53));
+    assert_eq!(fixes[phases.landing].time, hms(17, 22, 45));
+
+    let result = olc::optimize(&fixes[phases.free_flight()]).unwrap();
+    assert_approx_eq!(result.distance, 501.3, 0.1);
+}
+
+#[test]
+fn flight_phases_for_87i_qqk() {
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), hms(0, 0, 0));
+    let phases = phases::detect(&fixes).unwrap();
+    assert_eq!(phases.launch, LaunchMethod::Aerotow);
+    assert_eq!(fixes[phases.takeoff].time, hms(8, 55, 45));
+    assert_eq!(fixes[phases.release].time, hms(9, 2, 30));
+    assert_eq!(fixes[phases.landing].time, hms(19, 23, 25));
+}
+
+#[test]
+fn flight_phases_for_99b_7r9() {
+    let fixes = load_fixes(include_str!("fixtures/99bv7r92.igc"), hms(0, 0, 0));
+    let phases = phases::detect(&fixes).unwrap();
+    assert_eq!(phases.launch, LaunchMethod::Aerotow);
+    assert_eq!(fixes[phases.release].time, hms(16, 54, 28));
+}
+
+#[test]
+fn flight_phases_without_flight() {
+    let fixes: Vec<_> = (0..100)
+        .map(|i| Point { time: 36000 + i, latitude: 51., longitude: 7., altitude: 100, gnss_altitude: 100 })
+        .collect();
+
+    assert_eq!(phases::detect(&fixes), None);
+}
+
+#[test]
+fn timed_analyses_with_backwards_times() {
+    // a logger restart that goes back by ten minutes
+    let mut fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), hms(0, 0, 0));
+    fixes[3000..3050].iter_mut().for_each(|fix| fix.time -= 600);
+
+    assert_eq!(phases::detect(&fixes), None);
+    assert_eq!(engine::engine_runs(&fixes), None);
+    assert_eq!(outliers::find_outliers(&fixes, &OutlierOptions::default()), None);
+    assert_eq!(thermals::segments(&fixes), None);
+    assert_eq!(thermals::thermals(&fixes), None);
+}
+
+#[test]
+fn timed_analyses_without_fixes() {
+    let fixes: Vec<Point> = Vec::new();
+    assert_eq!(phases::detect(&fixes), None);
+    assert_eq!(engine::engine_runs(&fixes), Some((EngineSource::Climb, vec![])));
+    assert_eq!(outliers::find_outliers(&fixes, &OutlierOptions::default()), Some(vec![]));
+    assert_eq!(thermals::segments(&fixes), Some(vec![]));
+    assert_eq!(thermals::thermals(&fixes), Some(vec![]));
+
+    let filtered = outliers::remove_outliers(fixes, &OutlierOptions::default());
+    assert!(filtered.fixes.is_empty());
+    assert!(filtered.outliers.is_empty());
+}
+
+#[test]
+fn geodesic_flinders_peak_to_buninyong() {
+    // reference values from Vincenty (1975)
+    let flinders_peak = Point { time: 0, latitude: -37.951_033_416_7, longitude: 144.424_867_888_9, altitude: 0, gnss_altitude: 0 };
+    let buninyong = Point { time: 0, latitude: -37.652_821_138_9, longitude: 143.926_495_527_8, altitude: 0, gnss_altitude: 0 };
+
+    let inverse = geodesic::inverse(&flinders_peak, &buninyong);
+    assert_approx_eq!(inverse.distance, 54.972_271, 1e-6);
+    assert_approx_eq!(inverse.initial_bearing, 306.868_158_3, 1e-5);
+    assert_approx_eq!(inverse.final_bearing, 307.173_630_6, 1e-5);
+
+    let direct = geodesic::direct(&flinders_peak, inverse.initial_bearing, inverse.distance);
+    assert_approx_eq!(direct.latitude, buninyong.latitude, 1e-9);
+    assert_approx_eq!(direct.longitude, buninyong.longitude, 1e-9);
+    assert_approx_eq!(direct.final_bearing, inverse.final_bearing, 1e-6);
+
+    assert_eq!(geodesic::distance(&flinders_peak, &flinders_peak), 0.);
+}
+
+#[test]
+fn altitude_source_fallback() {
+    let fixes = [(0, 500), (0, 1500), (0, 700)].iter()
+        .map(|&(altitude, gnss_altitude)| Point { time: 0, latitude: 0., longitude: 0., altitude, gnss_altitude })
+        .collect::<Vec<_>>();
+
+    assert_eq!(altitude::altitudes(&fixes, AltitudeSource::Pressure), Some((AltitudeSource::Pressure, vec![0, 0, 0])));
+    assert_eq!(altitude::altitudes(&fixes, AltitudeSource::Gnss), Some((AltitudeSource::Gnss, vec![500, 1500, 700])));
+    assert_eq!(altitude::altitudes(&fixes, AltitudeSource::Auto), Some((AltitudeSource::Gnss, vec![500, 1500, 700])));
+}
+
+#[test]
+fn alternatives_for_87i_qqk() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let results = olc::alternatives(&fixes, &OptimizeOptions::default(), 3).unwrap();
+    assert_eq!(results.len(), 3);
+    assert_approx_eq!(results[0].distance, 780.42, 0.1);
+    assert_eq!(results[0].path, vec![1, 1129, 1666, 4348, 6070, 6681, 7194]);
+    assert!(results.windows(2).all(|pair| pair[0].distance >= pair[1].distance));
+    assert_approx_eq!(results[1].distance, 744.55, 0.1);
+    assert_approx_eq!(results[2].distance, 716.09, 0.1);
+
+    // no turnpoint lies within 5 km of a turnpoint of a better path
+    let turnpoints = |result: &OptimizationResult| result.path[1..result.path.len() - 1].to_vec();
+    for (i, result) in results.iter().enumerate() {
+        for better in &results[..i] {
+            assert!(turnpoints(result).iter().all(|&index| {
+                turnpoints(better).iter().all(|&other| haversine_distance_f64(&fixes[index], &fixes[other]) >= 5.)
+            }));
+        }
+    }
+}
+
+#[test]
+fn progress_for_87i_qqk() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+
+    let mut reports = Vec::new();
+    let token = CancellationToken::new();
+    let result = olc::optimize_with_progress(&fixes, &OptimizeOptions::default(), &token, |progress| {
+        reports.push(progress.clone());
+    }).unwrap();
+
+    assert_eq!(result.path, vec![1, 1129, 1666, 4348, 6070, 6681, 7194]);
+    assert!(!reports.is_empty());
+    assert!(reports.iter().all(|progress| match *progress {
+        Progress::SolutionGraph { leg, legs } => leg <= legs,
+        Progress::UpperBounds { level, levels, .. } => level <= levels,
+        Progress::StartCandidate { candidate, candidates, .. } => candidate <= candidates,
+        _ => true,
+    }));
+    assert!(reports.iter().any(|progress| matches!(progress, Progress::SolutionGraph { .. })));
+    assert!(reports.iter().any(|progress| matches!(progress, Progress::UpperBounds { .. })));
+}
+
+#[test]
+fn cancel_optimization_for_87i_qqk() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+
+    let token = CancellationToken::new();
+    let error = olc::optimize_with_progress(&fixes, &OptimizeOptions::default(), &token, |_| token.cancel())
+        .unwrap_err();
+
+    let cancelled = error.downcast::<Cancelled>().unwrap();
+    let best = cancelled.best.unwrap();
+    assert_eq!(best.path.len(), 7);
+    assert!(best.distance <= olc::optimize(&fixes).unwrap().distance);
+
+    let error = olc::optimize_with_progress(&fixes, &OptimizeOptions::default(), &token, |_| {}).unwrap_err();
+    assert!(error.downcast::<Cancelled>().is_ok());
+}
+
+#[test]
+fn optimize_with_zero_legs_fails() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let options = OptimizeOptions { legs: 0, ..OptimizeOptions::default() };
+    let error = olc::optimize_with(&fixes, &options).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::InvalidLegs);
+
+    let error = olc::alternatives(&fixes, &OptimizeOptions::default(), 0).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::InvalidCount);
+
+    let options = olc::SprintOptions { legs: 0, ..olc::SprintOptions::default() };
+    let error = olc::sprint_with(&fixes, &options).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::InvalidLegs);
+}
+
+#[test]
+fn optimize_invalid_tracks_fails() {
+    let release = hms(9, 2, 5);
+    let mut fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+
+    let error = olc::optimize(&fixes[..0]).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::EmptyTrack);
+
+    let error = olc::optimize(&fixes[..3]).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::TooFewFixes { fixes: 3, required: 7 });
+
+    let options = OptimizeOptions { altitude_rule: AltitudeRule::MaxHeightLoss(-5000), ..OptimizeOptions::default() };
+    let error = olc::optimize_with(&fixes, &options).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::NoValidSolution);
+
+    fixes[100].latitude = f64::NAN;
+    let error = olc::optimize(&fixes).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::NonFiniteCoordinates { index: 100 });
+}
+
+#[test]
+fn optimize_without_gnss_altitudes_or_times_fails() {
+    let fixes: Vec<_> = (0..10)
+        .map(|i| Position { latitude: 51. + i as f32 * 0.01, longitude: 7., altitude: 1000 })
+        .collect();
+
+    let options = OptimizeOptions { altitude_source: AltitudeSource::Gnss, ..OptimizeOptions::default() };
+    let error = olc::optimize_with(&fixes, &options).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::MissingGnssAltitude);
+
+    let options = OptimizeOptions { engine_rule: EngineRule::AfterLastRun, ..OptimizeOptions::default() };
+    let error = olc::optimize_with(&fixes, &options).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::MissingTimes);
+
+    let error = olc::sprint(&fixes).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::MissingTimes);
+}
+
+#[test]
+fn flat_points_without_fixes() {
+    let fixes: Vec<Position> = Vec::new();
+    assert!(flat::to_flat_points(&fixes).is_none());
+}
+
+#[test]
+fn sanitize_track_for_78e_6ng() {
+    let release = hms(10, 28, 5);
+    let fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release);
+
+    // a logger restart that repeats some fixes and a fix that is logged twice
+    let mut broken = fixes.clone();
+    broken.splice(3000..3000, fixes[2990..3010].iter().cloned());
+    broken.insert(5000, fixes[4980].clone());
+
+    // the optimizer skips the repeated fixes
+    let skipped = olc::optimize(&broken).unwrap();
+    let skipped_times: Vec<_> = skipped.path.iter().map(|&index| broken[index].time).collect();
+
+    let sanitized = track::sanitize(broken);
+    assert_eq!(sanitized.fixes, fixes);
+    assert_eq!(sanitized.issues.len(), 21);
+    assert_eq!(sanitized.issues[0], TimeIssue::Duplicate { index: 3000, time: fixes[2990].time });
+    assert_eq!(sanitized.issues[20], TimeIssue::Duplicate { index: 5001, time: fixes[4980].time });
+    assert!(!sanitized.issues.iter().any(|issue| matches!(issue, TimeIssue::OutOfOrder { .. })));
 
-    let fixes = file.lines()
-        .filter(|l| l.starts_with('B'))
-        .filter_map(|line| igc::records::BRecord::parse(&line).ok()
-            .map_or(None, |record| {
-                if record.timestamp.seconds_since_midnight() >= release.seconds_since_midnight() {
-                    Some(Point {
-                        latitude: record.pos.lat.into(),
-                        longitude: record.pos.lon.into(),
-                        altitude: record.pressure_alt,
-                    })
-                } else {
-                    None
-                }
-            }))
+    let result = olc::optimize(&sanitized.fixes).unwrap();
+    assert_approx_eq!(result.distance, 501.3, 0.1);
+    assert!(result.path.windows(2).all(|pair| sanitized.fixes[pair[0]].time < sanitized.fixes[pair[1]].time));
+
+    let times: Vec<_> = result.path.iter().map(|&index| sanitized.fixes[index].time).collect();
+    assert_eq!(skipped_times, times);
+    assert_eq!(skipped.distance, result.distance);
+}
+
+#[test]
+fn sanitize_track_out_of_order() {
+    let point = |time| Point { time, latitude: 51., longitude: 7., altitude: 100, gnss_altitude: 100 };
+    let sanitized = track::sanitize(vec![point(100), point(102), point(101), point(103), point(101)]);
+
+    let times: Vec<_> = sanitized.fixes.iter().map(|fix| fix.time).collect();
+    assert_eq!(times, vec![100, 101, 102, 103]);
+    assert_eq!(sanitized.issues, vec![
+        TimeIssue::OutOfOrder { index: 2, time: 101 },
+        TimeIssue::Duplicate { index: 4, time: 101 },
+    ]);
+}
+
+#[test]
+fn outliers_for_87i_qqk() {
+    let release = hms(9, 2, 5);
+    let mut fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    assert_eq!(outliers::find_outliers(&fixes, &OutlierOptions::default()), Some(vec![]));
+
+    // a GPS jump of 50 km
+    fixes[3000].latitude += 0.45;
+    let outliers = outliers::find_outliers(&fixes, &OutlierOptions::default()).unwrap();
+    assert_eq!(outliers, vec![Outlier { index: 3000, reason: OutlierReason::Speed }]);
+
+    let options = OptimizeOptions { ineligible_fixes: vec![3000], ..OptimizeOptions::default() };
+    let result = olc::optimize_with(&fixes, &options).unwrap();
+    assert_approx_eq!(result.distance, 780.42, 0.1);
+    assert_eq!(result.path, vec![1, 1129, 1666, 4348, 6070, 6681, 7194]);
+
+    let filtered = outliers::remove_outliers(fixes.clone(), &OutlierOptions::default());
+    assert_eq!(filtered.outliers, outliers);
+    assert_eq!(filtered.fixes.len(), fixes.len() - 1);
+    assert_eq!(filtered.fixes[3000], fixes[3001]);
+
+    // a GPS jump with the time of the fix before it
+    let mut fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let mut jump = fixes[3000].clone();
+    jump.latitude += 0.45;
+    fixes.insert(3001, jump);
+    assert_eq!(outliers::find_outliers(&fixes, &OutlierOptions::default()), None);
+
+    let filtered = outliers::remove_outliers(fixes.clone(), &OutlierOptions::default());
+    assert_eq!(filtered.fixes, fixes);
+    assert!(filtered.outliers.is_empty());
+}
+
+#[test]
+fn simplify_87i_qqk() {
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), hms(0, 0, 0));
+
+    let indices = simplify::douglas_peucker(&fixes, 0.5);
+    assert_eq!(indices.len(), 183);
+    assert_eq!(indices[0], 0);
+    assert_eq!(indices[indices.len() - 1], fixes.len() - 1);
+    assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let indices = simplify::resample(&fixes, 60).unwrap();
+    assert_eq!(indices.len(), 615);
+    assert_eq!(indices[indices.len() - 1], fixes.len() - 1);
+    assert!(indices.windows(2).rev().skip(1).all(|pair| fixes[pair[1]].time - fixes[pair[0]].time >= 60));
+}
+
+#[test]
+fn simplify_straight_line() {
+    let fixes: Vec<_> = (0..100)
+        .map(|i| Point { time: 36000 + i * 4, latitude: 51. + f64::from(i) * 0.001, longitude: 7., altitude: 1000, gnss_altitude: 1000 })
+        .collect();
+
+    assert_eq!(simplify::douglas_peucker(&fixes, 0.01), vec![0, 99]);
+    assert_eq!(simplify::resample(&fixes, 60).unwrap(), vec![0, 15, 30, 45, 60, 75, 90, 99]);
+    assert_eq!(simplify::resample(&fixes, u32::MAX).unwrap(), vec![0, 99]);
+}
+
+#[test]
+fn thermals_for_87i_qqk() {
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), hms(0, 0, 0));
+
+    let segments = thermals::segments(&fixes).unwrap();
+    assert_eq!(segments[0].start, 0);
+    assert_eq!(segments[segments.len() - 1].end, fixes.len() - 1);
+    assert!(segments.windows(2).all(|pair| pair[0].end + 1 == pair[1].start && pair[0].kind != pair[1].kind));
+
+    let thermals = thermals::thermals(&fixes).unwrap();
+    assert_eq!(thermals.len(), 67);
+    assert_eq!(thermals.len(), segments.iter().filter(|it| it.kind == SegmentKind::Circling).count());
+
+    let thermal = &thermals[0];
+    assert_eq!(fixes[thermal.entry].time, hms(9, 8, 32));
+    assert_eq!(fixes[thermal.exit].time, hms(9, 11, 27));
+    assert_eq!(thermal.altitude_gain, 311);
+    assert_approx_eq!(thermal.climb_rate, 1.78, 0.01);
+
+    // the thermals drift to the east
+    let thermal = &thermals[4];
+    assert_eq!(thermal.altitude_gain, 865);
+    assert_approx_eq!(thermal.drift_direction.unwrap(), 112., 1.);
+    assert_approx_eq!(thermal.drift_speed, 14.7, 0.1);
+}
+
+#[test]
+fn thermals_for_99b_7r9() {
+    // wave flight without circling
+    let fixes = load_fixes(include_str!("fixtures/99bv7r92.igc"), hms(0, 0, 0));
+    assert_eq!(thermals::thermals(&fixes), Some(vec![]));
+}
+
+#[test]
+fn live_optimizer_for_87i_qqk() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let num_fixes = fixes.len();
+    let batch = olc::optimize(&fixes).unwrap();
+
+    let mut optimizer = LiveOptimizer::new(OptimizeOptions::default()).unwrap();
+    assert!(optimizer.current_best().unwrap().is_none());
+
+    let mut last_distance = 0.;
+    for (i, fix) in fixes.into_iter().enumerate() {
+        assert!(optimizer.push(fix));
+        if (i + 1) % 500 == 0 {
+            let result = optimizer.current_best().unwrap().unwrap();
+            assert!(result.distance >= last_distance);
+            last_distance = result.distance;
+        }
+    }
+
+    assert_eq!(optimizer.fixes().len(), num_fixes);
+
+    let result = optimizer.current_best().unwrap().unwrap();
+    assert_approx_eq!(result.distance, 780.42, 0.1);
+    assert_eq!(result.path, vec![1, 1129, 1666, 4348, 6070, 6681, 7194]);
+
+    let result = optimizer.finish().unwrap();
+    assert_eq!(result.path, batch.path);
+    assert_eq!(result.distance, batch.distance);
+}
+
+#[test]
+fn live_optimizer_with_unsupported_options_fails() {
+    let options = OptimizeOptions { engine_rule: EngineRule::AfterLastRun, ..OptimizeOptions::default() };
+    assert_eq!(LiveOptimizer::<Point>::new(options).err(), Some(OptimizeError::UnsupportedEngineRule));
+
+    let options = OptimizeOptions { ineligible_fixes: vec![3000], ..OptimizeOptions::default() };
+    assert_eq!(LiveOptimizer::<Point>::new(options).err(), Some(OptimizeError::UnsupportedIneligibleFixes));
+
+    let options = OptimizeOptions { legs: 0, ..OptimizeOptions::default() };
+    assert_eq!(LiveOptimizer::<Point>::new(options).err(), Some(OptimizeError::InvalidLegs));
+}
+
+#[test]
+fn live_optimizer_ignores_invalid_fixes() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+
+    let mut optimizer = LiveOptimizer::new(OptimizeOptions::default()).unwrap();
+    for (i, fix) in fixes.iter().enumerate() {
+        assert!(optimizer.push(fix.clone()));
+        if i == 3000 {
+            assert!(!optimizer.push(fix.clone()));
+            assert!(!optimizer.push(fixes[2000].clone()));
+            assert!(!optimizer.push(Fix { time: fix.time + 1, latitude: f64::NAN, ..fix.clone() }));
+            assert!(!optimizer.push(Fix { time: fix.time + 1, longitude: f64::INFINITY, ..fix.clone() }));
+            assert!(optimizer.current_best().unwrap().is_some());
+        }
+    }
+
+    assert_eq!(optimizer.fixes().len(), fixes.len());
+
+    let result = optimizer.current_best().unwrap().unwrap();
+    assert_approx_eq!(result.distance, 780.42, 0.1);
+    assert_eq!(result.path, vec![1, 1129, 1666, 4348, 6070, 6681, 7194]);
+}
+
+#[test]
+fn triangle_for_78e_6ng() {
+    let release = hms(10, 28, 5);
+    let fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release);
+    let result = triangle::optimize(&fixes).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 275.86, 0.1);
+    assert_eq!(result.turnpoints, [1226, 2159, 3491]);
+    assert_eq!((result.start, result.finish), (131, 5037));
+}
+
+#[test]
+fn triangle_for_87i_qqk() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let result = triangle::optimize(&fixes).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 630.52, 0.1);
+    assert_eq!(result.turnpoints, [1667, 4348, 6071]);
+    assert_eq!((result.start, result.finish), (5, 7211));
+}
+
+#[test]
+fn flat_triangle_for_78e_6ng() {
+    let release = hms(10, 28, 5);
+    let fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release);
+    let result = triangle::optimize_flat(&fixes).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 300.31, 0.1);
+    assert_eq!(result.turnpoints, [1225, 3492, 4946]);
+    assert!(result.closing_distance <= 0.2 * result.distance);
+}
+
+#[test]
+fn triangle_and_out_and_return_invalid_tracks_fails() {
+    let release = hms(10, 28, 5);
+    let mut fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release);
+
+    let error = triangle::optimize(&fixes[..0]).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::EmptyTrack);
+
+    let error = triangle::optimize(&fixes[..2]).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::TooFewFixes { fixes: 2, required: 3 });
+
+    let error = out_and_return::optimize(&fixes[..2]).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::TooFewFixes { fixes: 2, required: 3 });
+
+    fixes[100].longitude = f64::INFINITY;
+    let error = triangle::optimize(&fixes).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::NonFiniteCoordinates { index: 100 });
+
+    let error = out_and_return::optimize(&fixes).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::NonFiniteCoordinates { index: 100 });
+}
+
+#[test]
+fn triangle_and_out_and_return_skip_duplicate_fixes() {
+    let release = hms(10, 28, 5);
+    let mut fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release);
+    fixes.insert(1001, fixes[1000].clone());
+
+    let result = triangle::optimize(&fixes).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 275.86, 0.1);
+    assert_eq!(result.turnpoints, [1227, 2160, 3492]);
+    assert_eq!((result.start, result.finish), (131, 5038));
+
+    let result = out_and_return::optimize(&fixes).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 230.95, 0.1);
+    assert_eq!((result.start, result.turnpoint, result.finish), (2081, 3493, 4948));
+}
+
+#[test]
+fn out_and_return_for_78e_6ng() {
+    let release = hms(10, 28, 5);
+    let fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release);
+    let result = out_and_return::optimize(&fixes).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 230.95, 0.1);
+    assert_eq!((result.start, result.turnpoint, result.finish), (2080, 3492, 4947));
+    assert!(result.closing_distance <= 0.2 * result.distance);
+}
+
+#[test]
+fn triangle_for_87i_qqk_with_double_precision_on_wgs84() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let options = triangle::TriangleOptions {
+        distance_formula: DistanceFormula::Wgs84,
+        precision: Precision::Double,
+        ..triangle::TriangleOptions::default()
+    };
+    let result = triangle::optimize_with(&fixes, &options).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 631.50, 0.1);
+    assert_eq!(result.turnpoints, [1667, 4348, 6071]);
+}
+
+#[test]
+fn out_and_return_for_78e_6ng_with_double_precision() {
+    let release = hms(10, 28, 5);
+    let fixes = load_fixes(include_str!("fixtures/2017-08-14-fla-6ng-01.igc"), release);
+    let options = out_and_return::OutAndReturnOptions { precision: Precision::Double, ..Default::default() };
+    let result = out_and_return::optimize_with(&fixes, &options).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 230.95, 0.1);
+    assert_eq!((result.start, result.turnpoint, result.finish), (2080, 3492, 4947));
+}
+
+#[test]
+fn sprint_for_87i_qqk_with_double_precision_on_wgs84() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let options = olc::SprintOptions {
+        distance_formula: DistanceFormula::Wgs84,
+        precision: Precision::Double,
+        ..olc::SprintOptions::default()
+    };
+    let result = olc::sprint_with(&fixes, &options).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 218.28, 0.1);
+    assert_eq!(result.path, vec![5308, 6071, 6263, 6681, 7036]);
+}
+
+#[test]
+fn sprint_invalid_tracks_fails() {
+    let release = hms(9, 2, 5);
+    let mut fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+
+    let error = olc::sprint(&fixes[..0]).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::EmptyTrack);
+
+    let error = olc::sprint(&fixes[..3]).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::TooFewFixes { fixes: 3, required: 5 });
+
+    // fixes that are not later than the fixes before them are skipped
+    fixes[200].time = fixes[100].time;
+    let result = olc::sprint(&fixes).unwrap().unwrap();
+    assert_eq!(result.path, vec![5308, 6071, 6263, 6681, 7036]);
+
+    fixes[100].latitude = f64::NAN;
+    let error = olc::sprint(&fixes).unwrap_err();
+    assert_eq!(error.downcast::<OptimizeError>().unwrap(), OptimizeError::NonFiniteCoordinates { index: 100 });
+}
+
+#[test]
+fn sprint_for_87i_qqk() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let result = olc::sprint(&fixes).unwrap().unwrap();
+    assert_approx_eq!(result.distance, 217.73, 0.1);
+    assert_approx_eq!(result.speed, 87.09, 0.1);
+    assert_eq!(result.path, vec![5308, 6071, 6263, 6681, 7036]);
+    assert!(result.finish_time - result.start_time <= 9000);
+}
+
+#[test]
+fn sprint_with_unlimited_window() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+
+    // both windows contain the whole track
+    let options = olc::SprintOptions { window: 24 * 60 * 60, ..olc::SprintOptions::default() };
+    let day = olc::sprint_with(&fixes[..100], &options).unwrap().unwrap();
+    let options = olc::SprintOptions { window: u32::MAX, ..olc::SprintOptions::default() };
+    let unlimited = olc::sprint_with(&fixes[..100], &options).unwrap().unwrap();
+    assert_eq!(unlimited.path, day.path);
+}
+
+#[test]
+fn sprint_for_87i_qqk_with_altitude_rules() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+
+    let sprint = |altitude_rule, altitude_source| {
+        let options = olc::SprintOptions { altitude_rule, altitude_source, ..olc::SprintOptions::default() };
+        olc::sprint_with(&fixes, &options).unwrap().unwrap()
+    };
+
+    let result = sprint(AltitudeRule::Disabled, AltitudeSource::Auto);
+    assert_approx_eq!(result.distance, 226.58, 0.1);
+    assert_eq!(result.height_loss, 1263);
+
+    let result = sprint(AltitudeRule::Penalty { max_height_loss: 0, penalty_per_meter: 0.01 }, AltitudeSource::Auto);
+    assert_approx_eq!(result.distance, 219.45, 0.1);
+    assert_approx_eq!(result.penalty, 3.86, 0.001);
+    assert_eq!(result.path, vec![5377, 6071, 6263, 6681, 7107]);
+
+    let result = sprint(AltitudeRule::MaxHeightLoss(0), AltitudeSource::Gnss);
+    assert_eq!(result.altitude_source, AltitudeSource::Gnss);
+    assert!(result.height_loss <= 0);
+    assert_approx_eq!(result.distance, 217.73, 0.1);
+}
+
+#[test]
+fn timeline_across_midnight() {
+    let fixes = [86390, 86395, 0, 5].iter()
+        .map(|&time| Point { time, latitude: 0., longitude: 0., altitude: 0, gnss_altitude: 0 })
         .collect::<Vec<_>>();
 
-    olc::optimize(&fixes).unwrap()
+    assert_eq!(time::timeline(&fixes), Some(vec![86390, 86395, 86400, 86405]));
+}
+
+#[test]
+fn olc_plus_for_87i_qqk() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let result = olc::plus(&fixes).unwrap();
+    assert_approx_eq!(result.classic.distance, 780.42, 0.1);
+    assert_approx_eq!(result.triangle.unwrap().distance, 630.52, 0.1);
+    assert_approx_eq!(result.triangle_points, 189.16, 0.1);
+    assert_approx_eq!(result.score, 969.58, 0.1);
+}
+
+#[test]
+fn olc_plus_for_87i_qqk_on_wgs84() {
+    let release = hms(9, 2, 5);
+    let fixes = load_fixes(include_str!("fixtures/87ilqqk1.igc"), release);
+    let options = olc::PlusOptions {
+        classic: OptimizeOptions { distance_formula: DistanceFormula::Wgs84, ..OptimizeOptions::default() },
+        triangle: triangle::TriangleOptions { distance_formula: DistanceFormula::Wgs84, ..Default::default() },
+        ..olc::PlusOptions::default()
+    };
+    let result = olc::plus_with(&fixes, &options).unwrap();
+    assert_approx_eq!(result.classic.distance, 781.80, 0.1);
+    assert_approx_eq!(result.triangle.unwrap().distance, 631.50, 0.1);
+}
+
+fn run_test(file: &str, release: u32) -> OptimizationResult {
+    olc::optimize(&load_fixes(file, release)).unwrap()
+}
+
+/// Returns the fixes of the IGC `file` from the `release` time on
+fn load_fixes(file: &str, release: u32) -> Vec<Fix> {
+    env_logger::try_init().ok();
+
+    Flight::parse(file).fixes.into_iter()
+        .filter(|fix| fix.time >= release)
+        .collect()
+}
+
+/// Returns the time in seconds since midnight
+fn hms(hours: u32, minutes: u32, seconds: u32) -> u32 {
+    (hours * 60 + minutes) * 60 + seconds
 }